@@ -0,0 +1,15 @@
+-- Add down migration script here
+
+ALTER TABLE blackjack_bot_rust_tables
+    DROP COLUMN decks,
+    DROP COLUMN dealer_hits_soft_17,
+    DROP COLUMN blackjack_payout,
+    DROP COLUMN double_after_split,
+    DROP COLUMN resplit,
+    DROP COLUMN max_split_hands,
+    DROP COLUMN split_aces_one_card,
+    DROP COLUMN surrender,
+    DROP COLUMN min_bet,
+    DROP COLUMN max_bet,
+    DROP COLUMN betting_time,
+    DROP COLUMN turn_timeout;
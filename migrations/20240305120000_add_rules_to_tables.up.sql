@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+ALTER TABLE blackjack_bot_rust_tables
+    ADD COLUMN decks INT NOT NULL DEFAULT 1,
+    ADD COLUMN dealer_hits_soft_17 BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN blackjack_payout TEXT NOT NULL DEFAULT '3:2',
+    ADD COLUMN double_after_split BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN resplit BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN max_split_hands INT NOT NULL DEFAULT 4,
+    ADD COLUMN split_aces_one_card BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN surrender TEXT NOT NULL DEFAULT 'late',
+    ADD COLUMN min_bet INT NOT NULL DEFAULT 1,
+    ADD COLUMN max_bet INT NOT NULL DEFAULT 10000,
+    ADD COLUMN betting_time INT NOT NULL DEFAULT 15,
+    ADD COLUMN turn_timeout INT NOT NULL DEFAULT 60;
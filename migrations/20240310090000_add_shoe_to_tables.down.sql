@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+ALTER TABLE blackjack_bot_rust_tables
+    DROP COLUMN penetration,
+    DROP COLUMN burn_card;
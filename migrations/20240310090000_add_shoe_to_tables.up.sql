@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+ALTER TABLE blackjack_bot_rust_tables
+    ADD COLUMN penetration INT NOT NULL DEFAULT 75,
+    ADD COLUMN burn_card BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,11 @@
+-- Add up migration script here
+
+CREATE TABLE blackjack_bot_rust_rounds (
+    id SERIAL PRIMARY KEY,
+    -- テーブルは閉じると削除されるので外部キーは張らない
+    table_id INT NOT NULL,
+    seed BIGINT NOT NULL,
+    shoe TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+ALTER TABLE blackjack_bot_rust_rounds
+    DROP COLUMN commitment,
+    DROP COLUMN server_seed,
+    DROP COLUMN client_seed;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+ALTER TABLE blackjack_bot_rust_rounds
+    ADD COLUMN commitment TEXT NOT NULL DEFAULT '',
+    ADD COLUMN server_seed TEXT,
+    ADD COLUMN client_seed TEXT;
@@ -0,0 +1,8 @@
+-- Add down migration script here
+
+DELETE FROM blackjack_bot_rust_rounds
+WHERE seed IS NULL OR shoe IS NULL;
+
+ALTER TABLE blackjack_bot_rust_rounds
+    ALTER COLUMN seed SET NOT NULL,
+    ALTER COLUMN shoe SET NOT NULL;
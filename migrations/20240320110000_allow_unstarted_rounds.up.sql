@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+-- ラウンドはベット受付の前に作るので、シードとシューは配るときに記録する
+ALTER TABLE blackjack_bot_rust_rounds
+    ALTER COLUMN seed DROP NOT NULL,
+    ALTER COLUMN shoe DROP NOT NULL;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+ALTER TABLE blackjack_bot_rust_users
+    DROP COLUMN reserved;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+-- ベット中の掛け金はラウンドが終わるまでreservedに移しておく
+ALTER TABLE blackjack_bot_rust_users
+    ADD COLUMN reserved INT NOT NULL DEFAULT 0;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE blackjack_bot_rust_ledger_entries;
+DROP TABLE blackjack_bot_rust_transactions;
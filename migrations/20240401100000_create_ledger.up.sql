@@ -0,0 +1,52 @@
+-- Add up migration script here
+
+-- コインの移動1回ごとに1行
+CREATE TABLE blackjack_bot_rust_transactions (
+    id SERIAL PRIMARY KEY,
+    kind TEXT NOT NULL,
+    user_id INT NOT NULL,
+    -- テーブルは閉じると削除されるので外部キーは張らない
+    table_id INT,
+    round_id INT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (user_id) REFERENCES blackjack_bot_rust_users(id),
+    FOREIGN KEY (round_id) REFERENCES blackjack_bot_rust_rounds(id)
+);
+
+-- 複式簿記の仕訳。1つの取引の仕訳の合計は必ず0になる
+-- accountはbalance, reserved（ユーザーの口座）とhouse, bonus（胴元の口座）のいずれか
+CREATE TABLE blackjack_bot_rust_ledger_entries (
+    id SERIAL PRIMARY KEY,
+    transaction_id INT NOT NULL,
+    user_id INT,
+    account TEXT NOT NULL,
+    amount INT NOT NULL,
+    FOREIGN KEY (transaction_id) REFERENCES blackjack_bot_rust_transactions(id),
+    FOREIGN KEY (user_id) REFERENCES blackjack_bot_rust_users(id)
+);
+
+CREATE INDEX ON blackjack_bot_rust_transactions (user_id);
+CREATE INDEX ON blackjack_bot_rust_ledger_entries (transaction_id);
+CREATE INDEX ON blackjack_bot_rust_ledger_entries (user_id, account);
+
+-- 既存の残高は胴元からの開始残高として記録する
+INSERT INTO blackjack_bot_rust_transactions (kind, user_id)
+SELECT 'opening', id
+FROM blackjack_bot_rust_users
+WHERE balance <> 0 OR reserved <> 0;
+
+INSERT INTO blackjack_bot_rust_ledger_entries (transaction_id, user_id, account, amount)
+SELECT t.id, u.id, 'balance', u.balance
+FROM blackjack_bot_rust_transactions t
+JOIN blackjack_bot_rust_users u ON u.id = t.user_id
+WHERE t.kind = 'opening' AND u.balance <> 0
+UNION ALL
+SELECT t.id, u.id, 'reserved', u.reserved
+FROM blackjack_bot_rust_transactions t
+JOIN blackjack_bot_rust_users u ON u.id = t.user_id
+WHERE t.kind = 'opening' AND u.reserved <> 0
+UNION ALL
+SELECT t.id, NULL, 'house', -(u.balance + u.reserved)
+FROM blackjack_bot_rust_transactions t
+JOIN blackjack_bot_rust_users u ON u.id = t.user_id
+WHERE t.kind = 'opening' AND u.balance + u.reserved <> 0;
@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DROP TABLE blackjack_bot_rust_bet_interactions;
+
+ALTER TABLE blackjack_bot_rust_rounds
+    DROP COLUMN settled_at;
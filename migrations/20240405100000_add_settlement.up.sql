@@ -0,0 +1,11 @@
+-- Add up migration script here
+
+-- 精算が終わったラウンドにはsettled_atを記録し、二重に精算しないようにする
+ALTER TABLE blackjack_bot_rust_rounds
+    ADD COLUMN settled_at TIMESTAMPTZ;
+
+-- 処理済みのベットのインタラクション。再送されたインタラクションで二重にベットしないようにする
+CREATE TABLE blackjack_bot_rust_bet_interactions (
+    interaction_id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
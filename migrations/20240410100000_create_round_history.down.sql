@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+DROP TABLE blackjack_bot_rust_round_hands;
+DROP TABLE blackjack_bot_rust_round_players;
+
+ALTER TABLE blackjack_bot_rust_rounds
+    DROP COLUMN dealer_cards;
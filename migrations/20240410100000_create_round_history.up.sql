@@ -0,0 +1,33 @@
+-- Add up migration script here
+
+ALTER TABLE blackjack_bot_rust_rounds
+    ADD COLUMN dealer_cards TEXT;
+
+-- 席順に並べたラウンドの参加者と結果
+CREATE TABLE blackjack_bot_rust_round_players (
+    id SERIAL PRIMARY KEY,
+    round_id INT NOT NULL,
+    seat INT NOT NULL,
+    user_id INT NOT NULL,
+    insurance INT NOT NULL,
+    even_money BOOLEAN NOT NULL,
+    payout INT NOT NULL,
+    net INT NOT NULL,
+    UNIQUE (round_id, seat),
+    FOREIGN KEY (round_id) REFERENCES blackjack_bot_rust_rounds(id),
+    FOREIGN KEY (user_id) REFERENCES blackjack_bot_rust_users(id)
+);
+
+-- スプリットした場合は1人で複数のハンドを持つ
+CREATE TABLE blackjack_bot_rust_round_hands (
+    id SERIAL PRIMARY KEY,
+    round_id INT NOT NULL,
+    seat INT NOT NULL,
+    hand_index INT NOT NULL,
+    cards TEXT NOT NULL,
+    amount INT NOT NULL,
+    split BOOLEAN NOT NULL,
+    surrendered BOOLEAN NOT NULL,
+    UNIQUE (round_id, seat, hand_index),
+    FOREIGN KEY (round_id, seat) REFERENCES blackjack_bot_rust_round_players(round_id, seat)
+);
@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+-- テーブルごとに全てのエフェクトを順番に記録する
+CREATE TABLE blackjack_bot_rust_events (
+    id SERIAL PRIMARY KEY,
+    -- テーブルは閉じると削除されるので外部キーは張らない
+    table_id INT NOT NULL,
+    seq INT NOT NULL,
+    version INT NOT NULL,
+    effect JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (table_id, seq)
+);
@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+-- 再起動で中断されたラウンドは無効にして掛け金を返金する
+ALTER TABLE blackjack_bot_rust_rounds
+    ADD COLUMN voided_at TIMESTAMPTZ;
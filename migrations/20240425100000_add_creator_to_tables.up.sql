@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+-- テーブルを閉じたり一時停止したりできるのは作成者と管理権限のあるユーザーのみ
+ALTER TABLE blackjack_bot_rust_tables
+    ADD COLUMN creator_id BIGINT;
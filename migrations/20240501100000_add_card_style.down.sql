@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+ALTER TABLE blackjack_bot_rust_users
+    DROP COLUMN card_style;
+
+ALTER TABLE blackjack_bot_rust_tables
+    DROP COLUMN card_style;
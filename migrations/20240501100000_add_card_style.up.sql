@@ -0,0 +1,8 @@
+-- Add up migration script here
+
+-- カードの表示形式。テーブルごとの既定とユーザーごとの設定
+ALTER TABLE blackjack_bot_rust_tables
+    ADD COLUMN card_style TEXT NOT NULL DEFAULT 'code';
+
+ALTER TABLE blackjack_bot_rust_users
+    ADD COLUMN card_style TEXT;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+-- ラウンドの途中でシャッフルし直したときのシューの中身。シャッフルした順に並べる
+ALTER TABLE blackjack_bot_rust_rounds
+    ADD COLUMN reshuffles TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+DROP INDEX blackjack_bot_rust_rounds_commitment_key;
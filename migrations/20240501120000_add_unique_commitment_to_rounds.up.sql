@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+-- ベットを受け付けるときとエフェクトを記録するときの両方からコミットメントでラウンドを探す
+CREATE UNIQUE INDEX blackjack_bot_rust_rounds_commitment_key
+    ON blackjack_bot_rust_rounds (commitment)
+    WHERE commitment <> '';
@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+-- シャッフルした後に捨てたカード。捨てていなければNULL
+ALTER TABLE blackjack_bot_rust_rounds
+    ADD COLUMN burn_card TEXT;
@@ -1,6 +1,14 @@
 use dotenvy::dotenv;
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
-use std::env;
+use sqlx::{postgres::PgPoolOptions, PgConnection, Pool, Postgres};
+use std::{env, fmt, str::FromStr};
+
+use crate::game::{
+    card::CardStyle,
+    event::{self, EFFECT_VERSION},
+    history::{HandRecord, PlayerRecord, RoundRecord},
+    rules::TableRules,
+    state::Effect,
+};
 
 pub enum UserId {
     Discord(u64),
@@ -47,6 +55,10 @@ pub fn discord_id_to_i64(id: u64) -> i64 {
     (id as i128 + i64::MIN as i128) as i64
 }
 
+pub fn i64_to_discord_id(id: i64) -> u64 {
+    (id as i128 - i64::MIN as i128) as u64
+}
+
 pub async fn establish_connection() -> Result<Pool<Postgres>, sqlx::Error> {
     dotenv().ok();
 
@@ -73,6 +85,63 @@ pub async fn create_user(pool: &Pool<Postgres>, name: String) -> Result<(), sqlx
     Ok(())
 }
 
+// ユーザーが選んだカードの表示形式。選んでいなければNone
+pub async fn get_card_style(
+    pool: &Pool<Postgres>,
+    discord_id: u64,
+) -> Result<Option<CardStyle>, sqlx::Error> {
+    let discord_id = discord_id_to_i64(discord_id);
+
+    let card_style = sqlx::query!(
+        r#"
+        SELECT card_style
+        FROM blackjack_bot_rust_users
+        WHERE id = (
+            SELECT user_id
+            FROM blackjack_bot_rust_discord_users
+            WHERE discord_id = $1
+        )
+        "#,
+        discord_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .and_then(|user| user.card_style);
+
+    card_style
+        .map(|style| style.parse())
+        .transpose()
+        .map_err(|e: String| sqlx::Error::Decode(e.into()))
+}
+
+pub async fn set_card_style(
+    pool: &Pool<Postgres>,
+    discord_id: u64,
+    card_style: CardStyle,
+) -> Result<(), sqlx::Error> {
+    let discord_id = discord_id_to_i64(discord_id);
+
+    sqlx::query!(
+        r#"
+        UPDATE blackjack_bot_rust_users
+        SET card_style = $2
+        WHERE id = (
+            SELECT user_id
+            FROM blackjack_bot_rust_discord_users
+            WHERE discord_id = $1
+        )
+        "#,
+        discord_id,
+        card_style.to_string()
+    )
+    .execute(pool)
+    .await
+    .and_then(|result| match result.rows_affected() {
+        0 => Err(sqlx::Error::RowNotFound),
+        _ => Ok(()),
+    })
+}
+
 pub async fn get_username_by_discord(
     pool: &Pool<Postgres>,
     discord_id: u64,
@@ -144,15 +213,74 @@ pub async fn get_balance(pool: &Pool<Postgres>, user_id: UserId) -> Result<i32,
     Ok(balance)
 }
 
-pub async fn create_table(pool: &Pool<Postgres>, channel_id: u64) -> Result<(), sqlx::Error> {
+pub async fn get_reserved(pool: &Pool<Postgres>, user_id: UserId) -> Result<i32, sqlx::Error> {
+    let user_id = user_id.get_user_id(pool).await?;
+
+    let reserved = sqlx::query!(
+        r#"
+        SELECT reserved
+        FROM blackjack_bot_rust_users
+        WHERE id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?
+    .reserved;
+
+    Ok(reserved)
+}
+
+pub async fn create_table(
+    pool: &Pool<Postgres>,
+    channel_id: u64,
+    creator_id: u64,
+    rules: &TableRules,
+    card_style: CardStyle,
+) -> Result<(), sqlx::Error> {
     let discord_channel_id = discord_id_to_i64(channel_id);
+    let creator_id = discord_id_to_i64(creator_id);
 
     sqlx::query!(
         r#"
-        INSERT INTO blackjack_bot_rust_tables (discord_channel_id)
-        VALUES ($1)
+        INSERT INTO blackjack_bot_rust_tables (
+            discord_channel_id,
+            decks,
+            penetration,
+            burn_card,
+            dealer_hits_soft_17,
+            blackjack_payout,
+            double_after_split,
+            resplit,
+            max_split_hands,
+            split_aces_one_card,
+            surrender,
+            min_bet,
+            max_bet,
+            betting_time,
+            turn_timeout,
+            creator_id,
+            card_style
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
         "#,
         discord_channel_id,
+        rules.decks as i32,
+        rules.penetration as i32,
+        rules.burn_card,
+        rules.dealer_hits_soft_17,
+        rules.blackjack_payout.to_string(),
+        rules.double_after_split,
+        rules.resplit,
+        rules.max_split_hands as i32,
+        rules.split_aces_one_card,
+        rules.surrender.to_string(),
+        rules.min_bet as i32,
+        rules.max_bet as i32,
+        rules.betting_time as i32,
+        rules.turn_timeout as i32,
+        creator_id,
+        card_style.to_string(),
     )
     .execute(pool)
     .await?;
@@ -160,6 +288,95 @@ pub async fn create_table(pool: &Pool<Postgres>, channel_id: u64) -> Result<(),
     Ok(())
 }
 
+// テーブルを作成したユーザー。作成者を記録する前のテーブルではNone
+pub async fn get_table_creator(
+    pool: &Pool<Postgres>,
+    table_id: i32,
+) -> Result<Option<u64>, sqlx::Error> {
+    let creator_id = sqlx::query!(
+        r#"
+        SELECT creator_id
+        FROM blackjack_bot_rust_tables
+        WHERE id = $1
+        "#,
+        table_id
+    )
+    .fetch_one(pool)
+    .await?
+    .creator_id;
+
+    Ok(creator_id.map(i64_to_discord_id))
+}
+
+// 登録されている全てのテーブルとそのルール、カードの表示形式を取得する
+pub async fn get_tables(
+    pool: &Pool<Postgres>,
+) -> Result<Vec<(i32, u64, TableRules, CardStyle)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            id,
+            discord_channel_id,
+            decks,
+            penetration,
+            burn_card,
+            dealer_hits_soft_17,
+            blackjack_payout,
+            double_after_split,
+            resplit,
+            max_split_hands,
+            split_aces_one_card,
+            surrender,
+            min_bet,
+            max_bet,
+            betting_time,
+            turn_timeout,
+            card_style
+        FROM blackjack_bot_rust_tables
+        ORDER BY id
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            let rules = TableRules {
+                decks: row.decks as u32,
+                penetration: row.penetration as u32,
+                burn_card: row.burn_card,
+                dealer_hits_soft_17: row.dealer_hits_soft_17,
+                blackjack_payout: row
+                    .blackjack_payout
+                    .parse()
+                    .map_err(|e: String| sqlx::Error::Decode(e.into()))?,
+                double_after_split: row.double_after_split,
+                resplit: row.resplit,
+                max_split_hands: row.max_split_hands as usize,
+                split_aces_one_card: row.split_aces_one_card,
+                surrender: row
+                    .surrender
+                    .parse()
+                    .map_err(|e: String| sqlx::Error::Decode(e.into()))?,
+                min_bet: row.min_bet as u32,
+                max_bet: row.max_bet as u32,
+                betting_time: row.betting_time as u64,
+                turn_timeout: row.turn_timeout as u64,
+            };
+            let card_style = row
+                .card_style
+                .parse()
+                .map_err(|e: String| sqlx::Error::Decode(e.into()))?;
+            Ok((
+                row.id,
+                i64_to_discord_id(row.discord_channel_id),
+                rules,
+                card_style,
+            ))
+        })
+        .collect()
+}
+
 pub async fn delete_table(pool: &Pool<Postgres>, table_id: i32) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
@@ -192,17 +409,67 @@ pub async fn get_table_id(pool: &Pool<Postgres>, channel_id: u64) -> Result<i32,
     Ok(table_id)
 }
 
-pub async fn bet(pool: &Pool<Postgres>, user_id: UserId, amount: i32) -> Result<(), sqlx::Error> {
-    let user_id = user_id.get_user_id(pool).await?;
+// ベット受付の前にラウンドを作り、サーバーシードのハッシュを記録する
+pub async fn create_round(
+    pool: &Pool<Postgres>,
+    table_id: i32,
+    commitment: String,
+) -> Result<i32, sqlx::Error> {
+    let round_id = sqlx::query!(
+        r#"
+        INSERT INTO blackjack_bot_rust_rounds (table_id, commitment)
+        VALUES ($1, $2)
+        ON CONFLICT (commitment) WHERE commitment <> ''
+        DO UPDATE SET commitment = EXCLUDED.commitment
+        RETURNING id
+        "#,
+        table_id,
+        commitment,
+    )
+    .fetch_one(pool)
+    .await?
+    .id;
 
+    Ok(round_id)
+}
+
+// ラウンドのシードと配る前のシューの中身を記録する
+pub async fn start_round(
+    pool: &Pool<Postgres>,
+    round_id: i32,
+    seed: u64,
+    shoe: String,
+) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
-        UPDATE blackjack_bot_rust_users
-        SET balance = balance - $1
+        UPDATE blackjack_bot_rust_rounds
+        SET seed = $1, shoe = $2, updated_at = NOW()
+        WHERE id = $3
+        "#,
+        seed as i64,
+        shoe,
+        round_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// ラウンドの途中でシャッフルし直したときのシューの中身を記録する
+pub async fn add_reshuffle(
+    pool: &Pool<Postgres>,
+    round_id: i32,
+    shoe: String,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE blackjack_bot_rust_rounds
+        SET reshuffles = array_append(reshuffles, $1), updated_at = NOW()
         WHERE id = $2
         "#,
-        amount,
-        user_id
+        shoe,
+        round_id
     )
     .execute(pool)
     .await?;
@@ -210,26 +477,761 @@ pub async fn bet(pool: &Pool<Postgres>, user_id: UserId, amount: i32) -> Result<
     Ok(())
 }
 
-pub async fn save_result(
+// シャッフルした後に捨てたカードを記録する
+pub async fn burn_card(
+    pool: &Pool<Postgres>,
+    round_id: i32,
+    card: String,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE blackjack_bot_rust_rounds
+        SET burn_card = $1, updated_at = NOW()
+        WHERE id = $2
+        "#,
+        card,
+        round_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// ラウンドの終了後に公開したシードを記録する
+pub async fn reveal_round(
+    pool: &Pool<Postgres>,
+    round_id: i32,
+    server_seed: String,
+    client_seed: String,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE blackjack_bot_rust_rounds
+        SET server_seed = $1, client_seed = $2, updated_at = NOW()
+        WHERE id = $3
+        "#,
+        server_seed,
+        client_seed,
+        round_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// テーブルのエフェクトを現在のバージョンの形式で記録する
+pub async fn append_event(
+    pool: &Pool<Postgres>,
+    table_id: i32,
+    seq: i32,
+    effect: &Effect,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO blackjack_bot_rust_events (table_id, seq, version, effect)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        table_id,
+        seq,
+        EFFECT_VERSION,
+        event::encode(effect),
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// テーブルの最初からseq番目までのエフェクトを順番に取得する
+pub async fn get_effects(
+    pool: &Pool<Postgres>,
+    table_id: i32,
+    seq: i32,
+) -> Result<Vec<Effect>, sqlx::Error> {
+    let events = sqlx::query!(
+        r#"
+        SELECT version, effect
+        FROM blackjack_bot_rust_events
+        WHERE table_id = $1 AND seq <= $2
+        ORDER BY seq
+        "#,
+        table_id,
+        seq
+    )
+    .fetch_all(pool)
+    .await?;
+
+    events
+        .into_iter()
+        .map(|event| {
+            event::decode(event.version, event.effect).map_err(|e| sqlx::Error::Decode(e.into()))
+        })
+        .collect()
+}
+
+// 次に記録するエフェクトの番号
+pub async fn get_next_seq(pool: &Pool<Postgres>, table_id: i32) -> Result<i32, sqlx::Error> {
+    let seq = sqlx::query!(
+        r#"
+        SELECT COALESCE(MAX(seq) + 1, 0) AS "seq!"
+        FROM blackjack_bot_rust_events
+        WHERE table_id = $1
+        "#,
+        table_id
+    )
+    .fetch_one(pool)
+    .await?
+    .seq;
+
+    Ok(seq)
+}
+
+pub struct Round {
+    pub commitment: String,
+    pub server_seed: Option<String>,
+    pub client_seed: Option<String>,
+    pub shoe: Option<String>,
+    pub reshuffles: Vec<String>,
+    pub burn_card: Option<String>,
+}
+
+pub async fn get_round(pool: &Pool<Postgres>, round_id: i32) -> Result<Round, sqlx::Error> {
+    let round = sqlx::query_as!(
+        Round,
+        r#"
+        SELECT commitment, server_seed, client_seed, shoe, reshuffles, burn_card
+        FROM blackjack_bot_rust_rounds
+        WHERE id = $1
+        "#,
+        round_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(round)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionKind {
+    Opening,
+    Bet,
+    Payout,
+    Bonus,
+    Refund,
+}
+
+impl fmt::Display for TransactionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransactionKind::Opening => write!(f, "opening"),
+            TransactionKind::Bet => write!(f, "bet"),
+            TransactionKind::Payout => write!(f, "payout"),
+            TransactionKind::Bonus => write!(f, "bonus"),
+            TransactionKind::Refund => write!(f, "refund"),
+        }
+    }
+}
+
+impl FromStr for TransactionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "opening" => Ok(TransactionKind::Opening),
+            "bet" => Ok(TransactionKind::Bet),
+            "payout" => Ok(TransactionKind::Payout),
+            "bonus" => Ok(TransactionKind::Bonus),
+            "refund" => Ok(TransactionKind::Refund),
+            _ => Err(format!("Unknown transaction kind: {}", s)),
+        }
+    }
+}
+
+// 仕訳の勘定。BalanceとReservedはユーザーごと、HouseとBonusは胴元の勘定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Account {
+    Balance,
+    Reserved,
+    House,
+    Bonus,
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Account::Balance => write!(f, "balance"),
+            Account::Reserved => write!(f, "reserved"),
+            Account::House => write!(f, "house"),
+            Account::Bonus => write!(f, "bonus"),
+        }
+    }
+}
+
+// 合計が0になる仕訳を1つの取引として記録し、ユーザーの残高に反映する
+async fn record(
+    conn: &mut PgConnection,
+    kind: TransactionKind,
+    user_id: i32,
+    table_id: Option<i32>,
+    round_id: Option<i32>,
+    entries: &[(Account, i32)],
+) -> Result<(), sqlx::Error> {
+    debug_assert_eq!(entries.iter().map(|(_, amount)| amount).sum::<i32>(), 0);
+
+    let transaction_id = sqlx::query!(
+        r#"
+        INSERT INTO blackjack_bot_rust_transactions (kind, user_id, table_id, round_id)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+        kind.to_string(),
+        user_id,
+        table_id,
+        round_id,
+    )
+    .fetch_one(&mut *conn)
+    .await?
+    .id;
+
+    let mut balance = 0;
+    let mut reserved = 0;
+    for (account, amount) in entries.iter().filter(|(_, amount)| *amount != 0) {
+        let owner = match account {
+            Account::Balance => {
+                balance += amount;
+                Some(user_id)
+            }
+            Account::Reserved => {
+                reserved += amount;
+                Some(user_id)
+            }
+            Account::House | Account::Bonus => None,
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO blackjack_bot_rust_ledger_entries (transaction_id, user_id, account, amount)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            transaction_id,
+            owner,
+            account.to_string(),
+            amount,
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE blackjack_bot_rust_users
+        SET balance = balance + $1, reserved = reserved + $2, updated_at = NOW()
+        WHERE id = $3
+        "#,
+        balance,
+        reserved,
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum ReserveError {
+    InsufficientBalance,
+    // 同じインタラクションで既に取り置いている
+    Duplicate,
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for ReserveError {
+    fn from(err: sqlx::Error) -> Self {
+        ReserveError::Database(err)
+    }
+}
+
+impl fmt::Display for ReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReserveError::InsufficientBalance => write!(f, "insufficient balance"),
+            ReserveError::Duplicate => write!(f, "duplicate interaction"),
+            ReserveError::Database(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+// 残高から掛け金を取り置く。残高が足りない場合は何もせずにエラーを返す。
+// インタラクションを渡した場合は取り置きと同じトランザクションで処理済みにし、二重に取り置かない
+pub async fn reserve(
     pool: &Pool<Postgres>,
     user_id: UserId,
     amount: i32,
+    table_id: i32,
+    round_id: i32,
+    interaction_id: Option<u64>,
+) -> Result<(), ReserveError> {
+    let user_id = user_id.get_user_id(pool).await?;
+    let mut tx = pool.begin().await?;
+
+    let balance = sqlx::query!(
+        r#"
+        SELECT balance
+        FROM blackjack_bot_rust_users
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .balance;
+
+    if balance < amount {
+        return Err(ReserveError::InsufficientBalance);
+    }
+
+    if let Some(interaction_id) = interaction_id {
+        let claimed = sqlx::query!(
+            r#"
+            INSERT INTO blackjack_bot_rust_bet_interactions (interaction_id)
+            VALUES ($1)
+            ON CONFLICT DO NOTHING
+            "#,
+            discord_id_to_i64(interaction_id)
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            == 1;
+        if !claimed {
+            return Err(ReserveError::Duplicate);
+        }
+    }
+
+    record(
+        &mut tx,
+        TransactionKind::Bet,
+        user_id,
+        Some(table_id),
+        Some(round_id),
+        &[(Account::Balance, -amount), (Account::Reserved, amount)],
+    )
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+// 取り置いた掛け金をそのまま残高に戻す
+pub async fn release(
+    pool: &Pool<Postgres>,
+    user_id: UserId,
+    amount: i32,
+    table_id: i32,
+    round_id: i32,
 ) -> Result<(), sqlx::Error> {
     let user_id = user_id.get_user_id(pool).await?;
+    let mut tx = pool.begin().await?;
+
+    record(
+        &mut tx,
+        TransactionKind::Refund,
+        user_id,
+        Some(table_id),
+        Some(round_id),
+        &[(Account::Reserved, -amount), (Account::Balance, amount)],
+    )
+    .await?;
+
+    tx.commit().await
+}
+
+// ラウンドの記録と精算を1つのトランザクションで行う。取り置いた掛け金を胴元に渡し、払い戻しを残高に加える
+// 精算済みのラウンドでは何もしないので、失敗した場合はそのままやり直せる
+pub async fn settle_round(
+    pool: &Pool<Postgres>,
+    table_id: i32,
+    round_id: i32,
+    round: &RoundRecord,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let settled = sqlx::query!(
+        r#"
+        SELECT settled_at IS NOT NULL AS "settled!"
+        FROM blackjack_bot_rust_rounds
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        round_id
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .settled;
+
+    if settled {
+        return Ok(());
+    }
+
+    for (seat, player) in round.players.iter().enumerate() {
+        let user_id = sqlx::query!(
+            r#"
+            SELECT id
+            FROM blackjack_bot_rust_users
+            WHERE name = $1
+            "#,
+            player.name
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .id;
+
+        let payout = player.payout as i32;
+        let stake = payout - player.net;
+        record(
+            &mut tx,
+            TransactionKind::Payout,
+            user_id,
+            Some(table_id),
+            Some(round_id),
+            &[
+                (Account::Reserved, -stake),
+                (Account::House, stake - payout),
+                (Account::Balance, payout),
+            ],
+        )
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO blackjack_bot_rust_round_players (
+                round_id, seat, user_id, insurance, even_money, payout, net
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            round_id,
+            seat as i32,
+            user_id,
+            player.insurance as i32,
+            player.even_money,
+            payout,
+            player.net,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for (index, hand) in player.hands.iter().enumerate() {
+            sqlx::query!(
+                r#"
+                INSERT INTO blackjack_bot_rust_round_hands (
+                    round_id, seat, hand_index, cards, amount, split, surrendered
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+                round_id,
+                seat as i32,
+                index as i32,
+                hand.cards,
+                hand.amount as i32,
+                hand.split,
+                hand.surrendered,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
 
     sqlx::query!(
         r#"
-        UPDATE blackjack_bot_rust_users
-        SET balance = balance + $1
+        UPDATE blackjack_bot_rust_rounds
+        SET dealer_cards = $1, settled_at = NOW(), updated_at = NOW()
         WHERE id = $2
         "#,
-        amount,
-        user_id
+        round.dealer_cards,
+        round_id
     )
-    .execute(pool)
+    .execute(&mut *tx)
     .await?;
 
-    Ok(())
+    tx.commit().await
+}
+
+// 精算済みのラウンドの記録を取得する
+pub async fn get_round_record(
+    pool: &Pool<Postgres>,
+    round_id: i32,
+) -> Result<RoundRecord, sqlx::Error> {
+    let dealer_cards = sqlx::query!(
+        r#"
+        SELECT dealer_cards
+        FROM blackjack_bot_rust_rounds
+        WHERE id = $1
+        "#,
+        round_id
+    )
+    .fetch_one(pool)
+    .await?
+    .dealer_cards
+    .ok_or(sqlx::Error::RowNotFound)?;
+
+    let mut players = sqlx::query!(
+        r#"
+        SELECT u.name, p.insurance, p.even_money, p.payout, p.net
+        FROM blackjack_bot_rust_round_players p
+        JOIN blackjack_bot_rust_users u ON u.id = p.user_id
+        WHERE p.round_id = $1
+        ORDER BY p.seat
+        "#,
+        round_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| PlayerRecord {
+        name: row.name,
+        insurance: row.insurance as u32,
+        even_money: row.even_money,
+        payout: row.payout as u32,
+        net: row.net,
+        hands: Vec::new(),
+    })
+    .collect::<Vec<_>>();
+
+    let hands = sqlx::query!(
+        r#"
+        SELECT seat, cards, amount, split, surrendered
+        FROM blackjack_bot_rust_round_hands
+        WHERE round_id = $1
+        ORDER BY seat, hand_index
+        "#,
+        round_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for hand in hands {
+        let player = players
+            .get_mut(hand.seat as usize)
+            .ok_or(sqlx::Error::RowNotFound)?;
+        player.hands.push(HandRecord {
+            cards: hand.cards,
+            amount: hand.amount as u32,
+            split: hand.split,
+            surrendered: hand.surrendered,
+        });
+    }
+
+    Ok(RoundRecord {
+        dealer_cards,
+        players,
+    })
+}
+
+// 精算も無効化もされていないラウンドとそのコミットメント
+pub async fn get_unsettled_rounds(
+    pool: &Pool<Postgres>,
+    table_id: i32,
+) -> Result<Vec<(i32, String)>, sqlx::Error> {
+    let rounds = sqlx::query!(
+        r#"
+        SELECT id, commitment
+        FROM blackjack_bot_rust_rounds
+        WHERE table_id = $1 AND settled_at IS NULL AND voided_at IS NULL
+        ORDER BY id
+        "#,
+        table_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| (row.id, row.commitment))
+    .collect();
+
+    Ok(rounds)
+}
+
+// コミットメントからラウンドと、精算済みかどうかを探す
+pub async fn find_round(
+    pool: &Pool<Postgres>,
+    commitment: &str,
+) -> Result<Option<(i32, bool)>, sqlx::Error> {
+    let round = sqlx::query!(
+        r#"
+        SELECT id, settled_at IS NOT NULL AS "settled!"
+        FROM blackjack_bot_rust_rounds
+        WHERE commitment = $1 AND commitment <> ''
+        "#,
+        commitment
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|row| (row.id, row.settled));
+
+    Ok(round)
+}
+
+// ラウンドを無効にし、取り置いたままの掛け金を返金する。返金したユーザーと金額を返す
+pub async fn void_round(
+    pool: &Pool<Postgres>,
+    table_id: i32,
+    round_id: i32,
+) -> Result<Vec<(String, i32)>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let finished = sqlx::query!(
+        r#"
+        SELECT settled_at IS NOT NULL OR voided_at IS NOT NULL AS "finished!"
+        FROM blackjack_bot_rust_rounds
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        round_id
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .finished;
+
+    if finished {
+        return Ok(Vec::new());
+    }
+
+    let stakes = sqlx::query!(
+        r#"
+        SELECT u.id, u.name, SUM(e.amount) AS "amount!"
+        FROM blackjack_bot_rust_ledger_entries e
+        JOIN blackjack_bot_rust_transactions t ON t.id = e.transaction_id
+        JOIN blackjack_bot_rust_users u ON u.id = e.user_id
+        WHERE t.round_id = $1 AND e.account = 'reserved'
+        GROUP BY u.id
+        HAVING SUM(e.amount) <> 0
+        "#,
+        round_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut refunds = Vec::new();
+    for stake in stakes {
+        let amount = stake.amount as i32;
+        record(
+            &mut tx,
+            TransactionKind::Refund,
+            stake.id,
+            Some(table_id),
+            Some(round_id),
+            &[(Account::Reserved, -amount), (Account::Balance, amount)],
+        )
+        .await?;
+        refunds.push((stake.name, amount));
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE blackjack_bot_rust_rounds
+        SET voided_at = NOW(), updated_at = NOW()
+        WHERE id = $1
+        "#,
+        round_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(refunds)
+}
+
+pub struct LedgerTransaction {
+    pub id: i32,
+    pub kind: String,
+    pub round_id: Option<i32>,
+    // 残高の増減
+    pub amount: i64,
+}
+
+// 新しい順に取引履歴を取得する
+pub async fn get_history(
+    pool: &Pool<Postgres>,
+    user_id: UserId,
+    limit: i64,
+) -> Result<Vec<LedgerTransaction>, sqlx::Error> {
+    let user_id = user_id.get_user_id(pool).await?;
+
+    let history = sqlx::query_as!(
+        LedgerTransaction,
+        r#"
+        SELECT t.id, t.kind, t.round_id, COALESCE(SUM(e.amount), 0) AS "amount!"
+        FROM blackjack_bot_rust_transactions t
+        LEFT JOIN blackjack_bot_rust_ledger_entries e
+            ON e.transaction_id = t.id AND e.account = 'balance'
+        WHERE t.user_id = $1
+        GROUP BY t.id
+        ORDER BY t.id DESC
+        LIMIT $2
+        "#,
+        user_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(history)
+}
+
+pub struct Discrepancy {
+    pub name: String,
+    pub balance: i32,
+    pub reserved: i32,
+    pub ledger_balance: i64,
+    pub ledger_reserved: i64,
+}
+
+// 台帳と食い違っている残高を台帳の値に合わせ、食い違っていたユーザーを返す
+pub async fn reconcile_balances(pool: &Pool<Postgres>) -> Result<Vec<Discrepancy>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let discrepancies = sqlx::query_as!(
+        Discrepancy,
+        r#"
+        SELECT
+            u.name,
+            u.balance,
+            u.reserved,
+            COALESCE(SUM(e.amount) FILTER (WHERE e.account = 'balance'), 0) AS "ledger_balance!",
+            COALESCE(SUM(e.amount) FILTER (WHERE e.account = 'reserved'), 0) AS "ledger_reserved!"
+        FROM blackjack_bot_rust_users u
+        LEFT JOIN blackjack_bot_rust_ledger_entries e ON e.user_id = u.id
+        GROUP BY u.id
+        HAVING
+            u.balance <> COALESCE(SUM(e.amount) FILTER (WHERE e.account = 'balance'), 0)
+            OR u.reserved <> COALESCE(SUM(e.amount) FILTER (WHERE e.account = 'reserved'), 0)
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for discrepancy in discrepancies.iter() {
+        sqlx::query!(
+            r#"
+            UPDATE blackjack_bot_rust_users
+            SET balance = $1, reserved = $2, updated_at = NOW()
+            WHERE name = $3
+            "#,
+            discrepancy.ledger_balance as i32,
+            discrepancy.ledger_reserved as i32,
+            discrepancy.name
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(discrepancies)
 }
 
 pub async fn create_bonus(pool: &Pool<Postgres>, amount: i32) -> Result<i32, sqlx::Error> {
@@ -264,6 +1266,7 @@ pub async fn get_bonus(
     bonus_id: i32,
 ) -> Result<i32, sqlx::Error> {
     let user_id = user_id.get_user_id(pool).await?;
+    let mut tx = pool.begin().await?;
 
     sqlx::query!(
         r#"
@@ -273,23 +1276,7 @@ pub async fn get_bonus(
         user_id,
         bonus_id,
     )
-    .execute(pool)
-    .await?;
-
-    sqlx::query!(
-        r#"
-        UPDATE blackjack_bot_rust_users
-        SET balance = balance + (
-            SELECT amount
-            FROM blackjack_bot_rust_bonuses
-            WHERE id = $1
-        )
-        WHERE id = $2
-        "#,
-        bonus_id,
-        user_id,
-    )
-    .execute(pool)
+    .execute(&mut *tx)
     .await?;
 
     let amount = sqlx::query!(
@@ -300,9 +1287,302 @@ pub async fn get_bonus(
         "#,
         bonus_id,
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *tx)
     .await?
     .amount;
 
+    record(
+        &mut tx,
+        TransactionKind::Bonus,
+        user_id,
+        None,
+        None,
+        &[(Account::Bonus, -amount), (Account::Balance, amount)],
+    )
+    .await?;
+
+    tx.commit().await?;
+
     Ok(amount)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ボーナスを受け取らせて残高を用意する
+    async fn funded_user(pool: &Pool<Postgres>, name: &str, amount: i32) {
+        create_user(pool, name.to_string()).await.unwrap();
+        let bonus_id = create_bonus(pool, amount).await.unwrap();
+        get_bonus(pool, UserId::Name(name.to_string()), bonus_id)
+            .await
+            .unwrap();
+    }
+
+    // nameが掛け金amountのハンドで勝ったラウンド
+    fn won_round(name: &str, amount: u32) -> RoundRecord {
+        RoundRecord {
+            dealer_cards: "H10 D8".to_string(),
+            players: vec![PlayerRecord {
+                name: name.to_string(),
+                insurance: 0,
+                even_money: false,
+                payout: amount * 2,
+                net: amount as i32,
+                hands: vec![HandRecord {
+                    cards: "S10 C9".to_string(),
+                    amount,
+                    split: false,
+                    surrendered: false,
+                }],
+            }],
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_reserve_above_balance(pool: Pool<Postgres>) {
+        funded_user(&pool, "a", 100).await;
+        let round_id = create_round(&pool, 1, "commitment".to_string())
+            .await
+            .unwrap();
+
+        let result = reserve(&pool, UserId::Name("a".to_string()), 101, 1, round_id, None).await;
+        assert!(matches!(result, Err(ReserveError::InsufficientBalance)));
+        assert_eq!(
+            get_balance(&pool, UserId::Name("a".to_string()))
+                .await
+                .unwrap(),
+            100
+        );
+        assert_eq!(
+            get_reserved(&pool, UserId::Name("a".to_string()))
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_concurrent_reserves(pool: Pool<Postgres>) {
+        funded_user(&pool, "a", 100).await;
+        let round_id = create_round(&pool, 1, "commitment".to_string())
+            .await
+            .unwrap();
+
+        let (first, second) = tokio::join!(
+            reserve(&pool, UserId::Name("a".to_string()), 60, 1, round_id, None),
+            reserve(&pool, UserId::Name("a".to_string()), 60, 1, round_id, None),
+        );
+        assert_eq!(
+            [first.is_ok(), second.is_ok()]
+                .iter()
+                .filter(|ok| **ok)
+                .count(),
+            1
+        );
+        assert_eq!(
+            get_balance(&pool, UserId::Name("a".to_string()))
+                .await
+                .unwrap(),
+            40
+        );
+        assert_eq!(
+            get_reserved(&pool, UserId::Name("a".to_string()))
+                .await
+                .unwrap(),
+            60
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_create_round_by_commitment(pool: Pool<Postgres>) {
+        let first = create_round(&pool, 1, "commitment".to_string())
+            .await
+            .unwrap();
+        let second = create_round(&pool, 1, "commitment".to_string())
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[sqlx::test]
+    async fn test_ledger_balances(pool: Pool<Postgres>) {
+        funded_user(&pool, "a", 100).await;
+        let round_id = create_round(&pool, 1, "commitment".to_string())
+            .await
+            .unwrap();
+        reserve(&pool, UserId::Name("a".to_string()), 30, 1, round_id, None)
+            .await
+            .unwrap();
+        reserve(&pool, UserId::Name("a".to_string()), 20, 1, round_id, None)
+            .await
+            .unwrap();
+        release(&pool, UserId::Name("a".to_string()), 20, 1, round_id)
+            .await
+            .unwrap();
+        settle_round(&pool, 1, round_id, &won_round("a", 30))
+            .await
+            .unwrap();
+
+        // どの取引も仕訳の合計は0になる
+        let unbalanced = sqlx::query!(
+            r#"
+            SELECT transaction_id
+            FROM blackjack_bot_rust_ledger_entries
+            GROUP BY transaction_id
+            HAVING SUM(amount) <> 0
+            "#
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert!(unbalanced.is_empty());
+
+        // ユーザーの残高は仕訳の合計と一致する
+        assert!(reconcile_balances(&pool).await.unwrap().is_empty());
+        assert_eq!(
+            get_balance(&pool, UserId::Name("a".to_string()))
+                .await
+                .unwrap(),
+            130
+        );
+        assert_eq!(
+            get_reserved(&pool, UserId::Name("a".to_string()))
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_settle_round_twice(pool: Pool<Postgres>) {
+        funded_user(&pool, "a", 100).await;
+        let round_id = create_round(&pool, 1, "commitment".to_string())
+            .await
+            .unwrap();
+        reserve(&pool, UserId::Name("a".to_string()), 30, 1, round_id, None)
+            .await
+            .unwrap();
+
+        let round = won_round("a", 30);
+        settle_round(&pool, 1, round_id, &round).await.unwrap();
+        settle_round(&pool, 1, round_id, &round).await.unwrap();
+
+        assert_eq!(
+            get_balance(&pool, UserId::Name("a".to_string()))
+                .await
+                .unwrap(),
+            130
+        );
+        assert_eq!(
+            get_reserved(&pool, UserId::Name("a".to_string()))
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            get_round_record(&pool, round_id)
+                .await
+                .unwrap()
+                .players
+                .len(),
+            1
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_void_round_refunds_once(pool: Pool<Postgres>) {
+        funded_user(&pool, "a", 100).await;
+        funded_user(&pool, "b", 200).await;
+        let round_id = create_round(&pool, 1, "commitment".to_string())
+            .await
+            .unwrap();
+        reserve(&pool, UserId::Name("a".to_string()), 30, 1, round_id, None)
+            .await
+            .unwrap();
+        reserve(&pool, UserId::Name("b".to_string()), 40, 1, round_id, None)
+            .await
+            .unwrap();
+        // 中断する前に返金済みの掛け金は返金し直さない
+        release(&pool, UserId::Name("b".to_string()), 40, 1, round_id)
+            .await
+            .unwrap();
+
+        let refunds = void_round(&pool, 1, round_id).await.unwrap();
+        assert_eq!(refunds, vec![("a".to_string(), 30)]);
+        assert!(void_round(&pool, 1, round_id).await.unwrap().is_empty());
+        assert!(get_unsettled_rounds(&pool, 1).await.unwrap().is_empty());
+
+        assert_eq!(
+            get_balance(&pool, UserId::Name("a".to_string()))
+                .await
+                .unwrap(),
+            100
+        );
+        assert_eq!(
+            get_balance(&pool, UserId::Name("b".to_string()))
+                .await
+                .unwrap(),
+            200
+        );
+        assert_eq!(
+            get_reserved(&pool, UserId::Name("a".to_string()))
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_reserve_claims_interaction_once(pool: Pool<Postgres>) {
+        funded_user(&pool, "a", 100).await;
+        let round_id = create_round(&pool, 1, "commitment".to_string())
+            .await
+            .unwrap();
+
+        // 取り置けなかったインタラクションは処理済みにならない
+        let result = reserve(
+            &pool,
+            UserId::Name("a".to_string()),
+            150,
+            1,
+            round_id,
+            Some(1),
+        )
+        .await;
+        assert!(matches!(result, Err(ReserveError::InsufficientBalance)));
+        reserve(
+            &pool,
+            UserId::Name("a".to_string()),
+            30,
+            1,
+            round_id,
+            Some(1),
+        )
+        .await
+        .unwrap();
+
+        let result = reserve(
+            &pool,
+            UserId::Name("a".to_string()),
+            30,
+            1,
+            round_id,
+            Some(1),
+        )
+        .await;
+        assert!(matches!(result, Err(ReserveError::Duplicate)));
+        assert_eq!(
+            get_balance(&pool, UserId::Name("a".to_string()))
+                .await
+                .unwrap(),
+            70
+        );
+        assert_eq!(
+            get_reserved(&pool, UserId::Name("a".to_string()))
+                .await
+                .unwrap(),
+            30
+        );
+    }
+}
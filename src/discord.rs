@@ -0,0 +1,401 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serenity::all::{ButtonStyle, ChannelId, Colour, MessageId};
+use serenity::builder::{
+    CreateActionRow, CreateAttachment, CreateButton, CreateEmbed, CreateEmbedFooter, CreateMessage,
+    EditAttachments, EditMessage,
+};
+use serenity::http::Http;
+
+use crate::game::card::CardStyle;
+use crate::game::rules::TableRules;
+use crate::game::state::{Effect, State};
+use crate::game::INSURANCE_TIME;
+use crate::render::{describe, RoundInfo, TableObserver};
+use crate::sprite;
+
+// 埋め込みに残す最近の出来事の数
+const LOG_SIZE: usize = 5;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+// テーブルの様子を1つの埋め込みにまとめ、エフェクトが届くたびに編集する
+pub struct DiscordRenderer {
+    http: Arc<Http>,
+    channel_id: ChannelId,
+    table_id: i32,
+    rules: TableRules,
+    style: CardStyle,
+    // テーブルの埋め込みを表示しているメッセージ。ラウンドをまたいで同じメッセージを編集する
+    message: Option<MessageId>,
+    // 最近の出来事
+    log: VecDeque<String>,
+    // ベットやインシュランスの受付期限、またはターンの制限時間（UNIX時間）
+    deadline: Option<u64>,
+    // 一時停止したときに残っていた秒数
+    paused: Option<u64>,
+    // 制限時間を数えているプレイヤーとハンドの番号
+    turn: Option<(String, usize)>,
+    commitment: Option<String>,
+    closed: bool,
+}
+
+impl DiscordRenderer {
+    pub fn new(
+        http: Arc<Http>,
+        channel_id: ChannelId,
+        table_id: i32,
+        rules: TableRules,
+        style: CardStyle,
+    ) -> DiscordRenderer {
+        DiscordRenderer {
+            http,
+            channel_id,
+            table_id,
+            rules,
+            style,
+            message: None,
+            log: VecDeque::new(),
+            deadline: None,
+            paused: None,
+            turn: None,
+            commitment: None,
+            closed: false,
+        }
+    }
+
+    fn push(&mut self, line: String) {
+        if self.log.len() >= LOG_SIZE {
+            self.log.pop_front();
+        }
+        self.log.push_back(line);
+    }
+
+    // エフェクトを適用した後の状態から、期限と出来事を更新する
+    fn update(&mut self, effect: &Effect, state: &State) {
+        match effect {
+            Effect::Init(_) => {
+                self.log.clear();
+                self.deadline = Some(now() + self.rules.betting_time);
+                self.paused = None;
+            }
+            Effect::Commit(hash) => self.commitment = Some(hash.clone()),
+            Effect::ExtendBetting(seconds) => self.deadline = Some(now() + seconds),
+            Effect::Start | Effect::DealerBlackjack | Effect::CloseInsurance | Effect::Finish => {
+                self.deadline = None
+            }
+            Effect::OfferInsurance | Effect::OfferSurrender => {
+                self.deadline = Some(now() + INSURANCE_TIME)
+            }
+            Effect::Pause => {
+                self.paused = self
+                    .deadline
+                    .take()
+                    .map(|deadline| deadline.saturating_sub(now()));
+            }
+            Effect::Resume => {
+                if let Some(remaining) = self.paused.take() {
+                    self.deadline = Some(now() + remaining);
+                }
+            }
+            Effect::Close | Effect::NoPlayer => {
+                self.deadline = None;
+                self.closed = true;
+            }
+            _ => {}
+        }
+        for line in describe(effect) {
+            self.push(line);
+        }
+
+        // 制限時間は別のプレイヤーかハンドの番になったときだけ数え直される
+        // 同じハンドでヒットやダブルダウンをしても延びない
+        let turn = state
+            .get_current_player()
+            .zip(state.get_current_hand_index())
+            .map(|(player, hand)| (player.name.clone(), hand));
+        if (*effect == Effect::NextPlayer || turn != self.turn) && !state.is_insurance() {
+            self.deadline = turn.as_ref().map(|_| now() + self.rules.turn_timeout);
+        }
+        self.turn = turn;
+    }
+
+    fn status(&self, state: &State) -> String {
+        let timer = self
+            .deadline
+            .map(|deadline| format!("（<t:{deadline}:R>に締め切り）"))
+            .unwrap_or_default();
+
+        if self.closed {
+            "テーブルを閉じました。".to_string()
+        } else if let Some(remaining) = self.paused {
+            format!("一時停止中です（残り{remaining}秒）。")
+        } else if state.is_betting() {
+            format!(
+                "参加・退室・ベットを受け付けています{timer}。全員が`/ready`すればすぐに始めます。"
+            )
+        } else if state.is_insurance() {
+            format!("インシュランスとサレンダーを受け付けています{timer}。")
+        } else if let Some(player) = state.get_current_player() {
+            format!("{}さんのターンです{timer}。", player.name)
+        } else if state.is_dealer_turn() {
+            "ディーラーのターンです。".to_string()
+        } else {
+            "ラウンドが終了しました。".to_string()
+        }
+    }
+
+    // テーブル全体の埋め込み
+    fn embed(&self, state: &State, round_id: i32) -> CreateEmbed {
+        let style = self.style;
+        let mut embed = CreateEmbed::new()
+            .title(format!("ブラックジャック ラウンド{round_id}"))
+            .description(self.status(state))
+            .colour(if self.closed {
+                Colour::DARK_GREY
+            } else {
+                Colour::DARK_GREEN
+            })
+            .field("ディーラー", dealer_field(state, style), false);
+
+        let players = state.get_player_order();
+        if players.is_empty() {
+            embed = embed.field("プレイヤー", "まだ誰も参加していません", false);
+        }
+        let current = state.get_current_player().map(|player| &player.name);
+        for name in players {
+            let mut title = name.clone();
+            if current == Some(&name) {
+                title = format!("▶ {title}");
+            } else if state.is_betting() && state.get_player(&name).unwrap().is_ready() {
+                title = format!("{title} ✅");
+            }
+            embed = embed.field(title, seat_field(state, &name, style), true);
+        }
+
+        if !self.log.is_empty() {
+            embed = embed.field(
+                "最近の出来事",
+                self.log.iter().cloned().collect::<Vec<_>>().join("\n"),
+                false,
+            );
+        }
+        if let Some(hash) = &self.commitment {
+            embed = embed.footer(CreateEmbedFooter::new(format!(
+                "サーバーシードのハッシュ: {hash}"
+            )));
+        }
+        embed
+    }
+
+    // 現在のターンのプレイヤーが選べる行動のボタン。一時停止中は押せなくする
+    fn components(&self, state: &State) -> Vec<CreateActionRow> {
+        let buttons = action_buttons(state, &self.rules);
+        if buttons.is_empty() || self.closed {
+            return vec![];
+        }
+        let paused = self.paused.is_some();
+        vec![CreateActionRow::Buttons(
+            buttons
+                .into_iter()
+                .map(|button| button.disabled(paused))
+                .collect(),
+        )]
+    }
+}
+
+fn dealer_field(state: &State, style: CardStyle) -> String {
+    let dealer = state.get_dealer();
+    if dealer.get_hand(0).get_card_count() == 0 {
+        "-".to_string()
+    } else {
+        dealer.get_hand(0).format(style)
+    }
+}
+
+// 席ごとのハンドと掛け金
+fn seat_field(state: &State, name: &str, style: CardStyle) -> String {
+    let player = state.get_player(name).unwrap();
+    let current = state
+        .get_current_player()
+        .filter(|current| current.name == name)
+        .and_then(|_| state.get_current_hand_index());
+
+    let mut lines = vec![];
+    for index in 0..player.get_hand_count() {
+        let hand = player.get_hand(index);
+        let amount = hand.get_amount();
+        let mut line = if hand.get_card_count() == 0 {
+            if amount == 0 {
+                "未ベット".to_string()
+            } else {
+                format!("ベット: {amount}")
+            }
+        } else {
+            format!("{} — {amount}", hand.format(style))
+        };
+        if hand.is_surrendered() {
+            line.push_str(" サレンダー");
+        }
+        if current == Some(index) && player.get_hand_count() > 1 {
+            line = format!("▶ {line}");
+        }
+        lines.push(line);
+    }
+    if player.get_insurance() > 0 {
+        lines.push(format!("インシュランス: {}", player.get_insurance()));
+    }
+    if player.has_even_money() {
+        lines.push("イーブンマネー".to_string());
+    }
+    lines.join("\n")
+}
+
+// 現在のハンドで選べる行動のボタン。custom_idは「行動:プレイヤー名」
+fn action_buttons(state: &State, rules: &TableRules) -> Vec<CreateButton> {
+    let name = match state.get_current_player() {
+        Some(player) => &player.name,
+        None => return vec![],
+    };
+
+    let mut buttons = vec![
+        CreateButton::new(format!("hit:{name}"))
+            .label("ヒット")
+            .style(ButtonStyle::Primary),
+        CreateButton::new(format!("stand:{name}"))
+            .label("スタンド")
+            .style(ButtonStyle::Secondary),
+    ];
+    if state.can_double_down(rules) {
+        buttons.push(
+            CreateButton::new(format!("double:{name}"))
+                .label("ダブルダウン")
+                .style(ButtonStyle::Success),
+        );
+    }
+    if state.can_split(rules) {
+        buttons.push(
+            CreateButton::new(format!("split:{name}"))
+                .label("スプリット")
+                .style(ButtonStyle::Success),
+        );
+    }
+    buttons
+}
+
+// ラウンドの結果の埋め込み。精算に失敗した場合はその旨を添える
+fn result_embed(
+    state: &State,
+    rules: &TableRules,
+    style: CardStyle,
+    round: &RoundInfo,
+    seeds: (&str, &str),
+) -> CreateEmbed {
+    let round_id = round.round_id;
+    let result = state.get_result(rules);
+    let mut embed = CreateEmbed::new()
+        .title(format!("ラウンド{round_id}の結果"))
+        .colour(Colour::GOLD)
+        .field("ディーラー", dealer_field(state, style), false);
+    if !round.settled {
+        embed = embed
+            .description("精算に失敗しました。掛け金はベット中のまま保留されています。")
+            .colour(Colour::RED);
+    }
+
+    for name in state.get_player_order() {
+        let (payout, net) = result[&name];
+        embed = embed.field(
+            name.clone(),
+            format!(
+                "{}\n払い戻し: {payout}（{net:+}）",
+                seat_field(state, &name, style)
+            ),
+            true,
+        );
+    }
+
+    let (server_seed, client_seed) = seeds;
+    embed.field(
+        "シード",
+        format!("サーバーシード: `{server_seed}`\nクライアントシード: `{client_seed}`\n`/verify {round_id}` でシャッフルを検証できます。\n`/hand {round_id}` でこのラウンドを確認できます。"),
+        false,
+    )
+}
+
+#[async_trait]
+impl TableObserver for DiscordRenderer {
+    async fn observe(&mut self, effect: &Effect, state: &State, round: &RoundInfo) {
+        self.update(effect, state);
+
+        // ラウンドの結果だけは新しいメッセージで送る
+        if let Effect::Reveal(server_seed, client_seed) = effect {
+            let embed = result_embed(
+                state,
+                &self.rules,
+                self.style,
+                round,
+                (server_seed, client_seed),
+            );
+            if let Err(why) = self
+                .channel_id
+                .send_message(&self.http, CreateMessage::new().embed(embed))
+                .await
+            {
+                println!("Cannot send the result of round {}: {why}", round.round_id);
+            }
+        }
+    }
+
+    async fn flush(&mut self, state: &State, round: &RoundInfo) {
+        let table_id = self.table_id;
+        let mut embed = self.embed(state, round.round_id);
+        let components = self.components(state);
+        // 配られたカードの画像を埋め込みに添える
+        let image =
+            sprite::render_table(state).map(|png| CreateAttachment::bytes(png, "table.png"));
+        if image.is_some() {
+            embed = embed.attachment("table.png");
+        }
+
+        match self.message {
+            Some(message_id) => {
+                let attachments = match image {
+                    Some(image) => EditAttachments::new().add(image),
+                    None => EditAttachments::new(),
+                };
+                let builder = EditMessage::new()
+                    .embed(embed)
+                    .components(components)
+                    .attachments(attachments);
+                if let Err(why) = self
+                    .channel_id
+                    .edit_message(&self.http, message_id, builder)
+                    .await
+                {
+                    // 消されたメッセージは編集できないので、次は新しく送る
+                    println!("Cannot update the table {table_id}: {why}");
+                    self.message = None;
+                }
+            }
+            None => {
+                let mut builder = CreateMessage::new().embed(embed).components(components);
+                if let Some(image) = image {
+                    builder = builder.add_file(image);
+                }
+                match self.channel_id.send_message(&self.http, builder).await {
+                    Ok(message) => self.message = Some(message.id),
+                    Err(why) => println!("Cannot show the table {table_id}: {why}"),
+                }
+            }
+        }
+    }
+}
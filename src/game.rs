@@ -1,13 +1,21 @@
+use async_trait::async_trait;
 use tokio::{
     select,
     sync::{broadcast, mpsc},
+    time::{sleep_until, Duration, Instant},
 };
 
-use self::{state::Effect, table::Command};
+use self::{rules::TableRules, state::Effect, table::Command};
 
-mod card;
+pub mod card;
 mod deck;
+pub mod event;
+pub mod fair;
+mod hand;
+pub mod history;
 mod player;
+pub mod rules;
+mod shoe;
 pub mod state;
 mod status;
 pub mod table;
@@ -20,80 +28,166 @@ pub struct Response {
 pub struct Request {
     res_tx: tokio::sync::oneshot::Sender<Response>,
     command: Command,
+    // 同じリクエストが二重に届いたときに見分ける番号。掛け金と一緒に記録する
+    id: Option<u64>,
 }
 
 impl Request {
     pub fn new(res_tx: tokio::sync::oneshot::Sender<Response>, command: Command) -> Self {
-        Self { res_tx, command }
+        Self {
+            res_tx,
+            command,
+            id: None,
+        }
+    }
+
+    pub fn with_id(
+        res_tx: tokio::sync::oneshot::Sender<Response>,
+        command: Command,
+        id: u64,
+    ) -> Self {
+        Self {
+            res_tx,
+            command,
+            id: Some(id),
+        }
     }
 }
 
-pub const BETTING_TIME: u64 = 15;
+// 掛け金を取り置く先。roundはラウンドのコミットメント、idはリクエストの番号
+#[async_trait]
+pub trait Escrow: Send {
+    async fn reserve(
+        &mut self,
+        round: &str,
+        name: &str,
+        amount: u32,
+        id: Option<u64>,
+    ) -> Result<(), String>;
+    async fn release(&mut self, round: &str, name: &str, amount: u32);
+}
+
+// 掛け金を取り置いてからコマンドを適用する。取り置けなければコマンドを受け付けない
+async fn apply_command(
+    table: &mut table::Table,
+    escrow: &mut dyn Escrow,
+    command: Command,
+    id: Option<u64>,
+) -> Result<Vec<Effect>, String> {
+    let stake = table.get_stake(&command)?;
+    let round = table.get_commitment();
+    let name = command.get_name().to_string();
+    if let Some(amount) = stake {
+        escrow.reserve(&round, &name, amount, id).await?;
+    }
+
+    let result = table.apply_command(command);
+    if let (Err(_), Some(amount)) = (&result, stake) {
+        escrow.release(&round, &name, amount).await;
+    }
+    result
+}
+
+pub const INSURANCE_TIME: u64 = 10;
+// 締め切り間際に参加したプレイヤーがベットできるように、受付時間を少なくともこれだけ残す
+pub const LATE_JOIN_TIME: u64 = 10;
+// ターンの制限時間の何秒前に知らせるか
+pub const TURN_WARNING_TIME: u64 = 10;
+// この回数続けて時間切れになったプレイヤーは次のラウンドで席から外す
+pub const TIMEOUT_LIMIT: u32 = 2;
+// この回数続けてベットせずに見送ったプレイヤーは次のラウンドで席から外す
+pub const SKIP_LIMIT: u32 = 3;
 
 pub async fn run(
     mut game_rx: mpsc::Receiver<Request>,
     broadcast_tx: broadcast::Sender<Effect>,
+    rules: TableRules,
+    mut escrow: Box<dyn Escrow>,
 ) -> Result<(), String> {
-    let mut players: Vec<String> = vec![];
+    let mut table = table::Table::new(rules.clone());
 
     loop {
-        let mut table = table::Table::new();
-
-        table.init_players(players.clone());
-        broadcast_tx
-            .send(Effect::Init(table.get_player_order()))
-            .unwrap();
+        for effect in table.init_round() {
+            broadcast_tx.send(effect).unwrap();
+        }
 
-        let (start_tx, mut start_rx) = mpsc::channel(1);
-        tokio::spawn(async move {
-            tokio::time::sleep(tokio::time::Duration::from_secs(BETTING_TIME)).await;
-            start_tx.send(()).await.unwrap();
-        });
+        // ベットやインシュランスの受付期限、またはターンの制限時間
+        let mut deadline = Some(Instant::now() + Duration::from_secs(rules.betting_time));
+        // ターンの制限時間が近いことを知らせる時刻
+        let mut warning = None;
+        // 一時停止したときに残っていた時間
+        let mut paused = None;
+        // 制限時間を数えているターン
+        let mut turn = None;
 
         loop {
-            let request = if table.is_started() {
-                game_rx.recv().await.unwrap()
-            } else {
-                let request = select! {
-                    request = game_rx.recv() => request.unwrap(),
-                    _ = start_rx.recv() => {
-                        if table.get_player_count() == 0 {
-                            broadcast_tx.send(Effect::NoPlayer).unwrap();
-                            return Ok(());
-                        }
-
-                        let effects = table.start()?;
-                        for effect in effects {
-                            broadcast_tx.send(effect).unwrap();
+            let timer = sleep_until(warning.or(deadline).unwrap_or_else(Instant::now));
+            select! {
+                request = game_rx.recv() => {
+                    let request = request.unwrap();
+                    let command = request.command.clone();
+                    let (acted, content) = match apply_command(&mut table, escrow.as_mut(), command, request.id).await {
+                        Ok(effects) => {
+                            let acted = !effects.is_empty();
+                            for effect in effects {
+                                broadcast_tx.send(effect).unwrap();
+                            }
+                            (acted, request.command.success_message())
                         }
+                        Err(err) => (false, err),
+                    };
 
-                        if table.is_finished() {
-                            players = table.get_players();
-                            break;
-                        }
+                    let response = Response { content };
+                    request.res_tx.send(response).unwrap();
 
-                        continue;
+                    if table.is_stopping() && table.is_betting() {
+                        broadcast_tx.send(Effect::Close).unwrap();
+                        return Ok(());
                     }
-                };
-                request
-            };
 
-            let content = match table.apply_command(request.command.clone()) {
-                Ok(effects) => {
+                    if table.is_all_ready() {
+                        // 全員が準備完了したらすぐに配る
+                        deadline = Some(Instant::now());
+                    } else if acted && matches!(request.command, Command::Participate(_)) {
+                        // 締め切り間際に参加した場合は受付時間を延ばす
+                        let extended = Instant::now() + Duration::from_secs(LATE_JOIN_TIME);
+                        if deadline.is_some_and(|deadline| deadline < extended) {
+                            deadline = Some(extended);
+                            broadcast_tx.send(Effect::ExtendBetting(LATE_JOIN_TIME)).unwrap();
+                        }
+                    }
+                }
+                _ = timer, if deadline.is_some() => {
+                    let effects = if warning.take().is_some() {
+                        table.warn_turn(TURN_WARNING_TIME)
+                    } else {
+                        deadline = None;
+
+                        if table.is_playing() {
+                            table.time_out()?
+                        } else if table.is_insurance() {
+                            table.close_insurance()?
+                        } else {
+                            if table.get_player_count() == 0 {
+                                broadcast_tx.send(Effect::NoPlayer).unwrap();
+                                return Ok(());
+                            }
+
+                            table.start()?
+                        }
+                    };
                     for effect in effects {
                         broadcast_tx.send(effect).unwrap();
                     }
-                    request.command.success_message()
-                }
-                Err(err) => err,
-            };
 
-            let response = Response { content };
-            request.res_tx.send(response).unwrap();
+                    // 誰もベットせずに見送ったラウンドは配らずに終える
+                    if deadline.is_none() && table.is_betting() {
+                        break;
+                    }
+                }
+            }
 
             if table.is_finished() {
-                broadcast_tx.send(Effect::Finish).unwrap();
-                players = table.get_players();
                 break;
             }
 
@@ -102,9 +196,47 @@ pub async fn run(
                 for effect in effects {
                     broadcast_tx.send(effect).unwrap();
                 }
-                players = table.get_players();
                 break;
             }
+
+            if table.is_insurance() {
+                if deadline.is_none() && paused.is_none() {
+                    deadline = Some(Instant::now() + Duration::from_secs(INSURANCE_TIME));
+                }
+            } else if table.is_playing() && table.get_turn() != turn {
+                // 制限時間は新しい席かハンドの番になったときだけ数え直す
+                turn = table.get_turn();
+                let timeout = Duration::from_secs(rules.turn_timeout);
+                let now = Instant::now();
+                deadline = Some(now + timeout);
+                warning = timeout
+                    .checked_sub(Duration::from_secs(TURN_WARNING_TIME))
+                    .filter(|before| !before.is_zero())
+                    .map(|before| now + before);
+            }
+
+            // 一時停止中は期限を止めておき、再開したら残りの時間から数え直す
+            if table.is_paused() {
+                if let Some(deadline) = deadline.take() {
+                    paused = Some(deadline.saturating_duration_since(Instant::now()));
+                    warning = None;
+                }
+            } else if let Some(remaining) = paused.take() {
+                let now = Instant::now();
+                deadline = Some(now + remaining);
+                if table.is_playing() {
+                    warning = remaining
+                        .checked_sub(Duration::from_secs(TURN_WARNING_TIME))
+                        .filter(|before| !before.is_zero())
+                        .map(|before| now + before);
+                }
+            }
+        }
+
+        // 閉じる指示があればラウンドの後にテーブルを閉じる
+        if table.is_stopping() {
+            broadcast_tx.send(Effect::Close).unwrap();
+            return Ok(());
         }
     }
 }
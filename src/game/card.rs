@@ -1,8 +1,9 @@
 use std::{fmt, str::FromStr};
 
-use strum::EnumIter;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use strum::{EnumIter, IntoEnumIterator};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Card {
     suit: Suit,
     value: Value,
@@ -20,6 +21,14 @@ impl Card {
         }
     }
 
+    pub fn get_suit(&self) -> Suit {
+        self.suit
+    }
+
+    pub fn get_value(&self) -> Value {
+        self.value
+    }
+
     pub fn get_score(&self, ace_high: bool) -> u32 {
         match self.value {
             Value::Ace => {
@@ -49,6 +58,61 @@ impl Card {
         matches!(self.value, Value::Ace)
     }
 
+    pub fn is_hidden(&self) -> bool {
+        self.suit == Suit::Hidden || self.value == Value::Hidden
+    }
+
+    // 指定した形式で表示する。どの形式もfrom_strで読み戻せる
+    pub fn format(&self, style: CardStyle) -> String {
+        match style {
+            CardStyle::Code => self.to_string(),
+            CardStyle::Symbol if self.is_hidden() => "?".to_string(),
+            CardStyle::Symbol => format!("{}{}", self.suit.symbol(), self.value),
+            CardStyle::Unicode => {
+                let code = match self.suit {
+                    Suit::Spade => 0x1F0A0,
+                    Suit::Heart => 0x1F0B0,
+                    Suit::Diamond => 0x1F0C0,
+                    Suit::Club => 0x1F0D0,
+                    Suit::Hidden => return "🂠".to_string(),
+                };
+                match self.value.rank() {
+                    Some(rank) => char::from_u32(code + rank).unwrap().to_string(),
+                    None => "🂠".to_string(),
+                }
+            }
+            CardStyle::Emoji if self.is_hidden() => "❓".to_string(),
+            CardStyle::Emoji => format!("{}\u{FE0F}{}", self.suit.symbol(), self.value.emoji()),
+        }
+    }
+
+    // トランプの絵文字（U+1F0A0〜）を読む
+    fn from_glyph(glyph: char) -> Option<Card> {
+        let code = glyph as u32;
+        let suit = match code & !0xF {
+            0x1F0A0 => Suit::Spade,
+            0x1F0B0 => Suit::Heart,
+            0x1F0C0 => Suit::Diamond,
+            0x1F0D0 => Suit::Club,
+            _ => return None,
+        };
+        let value = Value::iter().find(|value| value.rank() == Some(code & 0xF))?;
+        Some(Card::new(suit, value))
+    }
+
+    // "♠A"や"♠️🇦"のようにスートの記号から始まる形式を読む
+    fn from_symbol(s: &str) -> Option<Card> {
+        let mut chars = s.chars();
+        let symbol = chars.next()?;
+        let suit = Suit::iter().find(|suit| suit.symbol() == symbol)?;
+        let rest = chars.as_str();
+        let rest = rest.strip_prefix('\u{FE0F}').unwrap_or(rest);
+        let value = Value::iter()
+            .filter(|value| *value != Value::Hidden)
+            .find(|value| value.to_string() == rest || value.emoji() == rest)?;
+        Some(Card::new(suit, value))
+    }
+
     pub fn ace_diff() -> u32 {
         let card = Card::new(Suit::Spade, Value::Ace);
 
@@ -60,6 +124,26 @@ impl FromStr for Card {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // 伏せたカードは形式ごとに表し方が異なる
+        if matches!(s, "?" | "❓" | "🂠") {
+            return Ok(Card::new_hidden());
+        }
+
+        // 記号や絵文字の形式
+        if !s.is_ascii() {
+            let mut chars = s.chars();
+            let card = match (chars.next(), chars.next()) {
+                (Some(glyph), None) => Card::from_glyph(glyph),
+                _ => Card::from_symbol(s),
+            };
+            return card.ok_or("Invalid card".to_string());
+        }
+
+        // 保存されたデータから読み込むこともあるので、スライスする前に長さを確かめる
+        if !(2..=3).contains(&s.len()) {
+            return Err("Invalid card".to_string());
+        }
+
         // 10の場合もあるので、最初の1文字をスートとして取得する
         let suit = Suit::from_str(&s[0..1])?;
 
@@ -76,11 +160,25 @@ impl FromStr for Card {
 
 impl fmt::Display for Card {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}{}", self.suit.to_string(), self.value.to_string())
+        write!(f, "{}{}", self.suit, self.value)
     }
 }
 
-#[derive(Debug, EnumIter, Copy, Clone, PartialEq)]
+// 保存するときは"SA"のような文字列にする
+impl Serialize for Card {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Card {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Card::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, EnumIter, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Suit {
     Spade,
     Heart,
@@ -104,20 +202,32 @@ impl FromStr for Suit {
     }
 }
 
-impl ToString for Suit {
-    fn to_string(&self) -> String {
+impl Suit {
+    fn symbol(&self) -> char {
         match self {
+            Suit::Spade => '♠',
+            Suit::Heart => '♥',
+            Suit::Diamond => '♦',
+            Suit::Club => '♣',
+            Suit::Hidden => '?',
+        }
+    }
+}
+
+impl fmt::Display for Suit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
             Suit::Spade => "S",
             Suit::Heart => "H",
             Suit::Diamond => "D",
             Suit::Club => "C",
             Suit::Hidden => "X",
-        }
-        .to_string()
+        };
+        write!(f, "{}", s)
     }
 }
 
-#[derive(Debug, EnumIter, Copy, Clone, PartialEq)]
+#[derive(Debug, EnumIter, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Value {
     Ace,
     Two,
@@ -159,9 +269,50 @@ impl FromStr for Value {
     }
 }
 
-impl ToString for Value {
-    fn to_string(&self) -> String {
+impl Value {
+    // トランプの絵文字の中での番号。12番目のナイトは使わない
+    fn rank(&self) -> Option<u32> {
         match self {
+            Value::Ace => Some(1),
+            Value::Two => Some(2),
+            Value::Three => Some(3),
+            Value::Four => Some(4),
+            Value::Five => Some(5),
+            Value::Six => Some(6),
+            Value::Seven => Some(7),
+            Value::Eight => Some(8),
+            Value::Nine => Some(9),
+            Value::Ten => Some(10),
+            Value::Jack => Some(11),
+            Value::Queen => Some(13),
+            Value::King => Some(14),
+            Value::Hidden => None,
+        }
+    }
+
+    fn emoji(&self) -> &'static str {
+        match self {
+            Value::Ace => "🇦",
+            Value::Two => "2️⃣",
+            Value::Three => "3️⃣",
+            Value::Four => "4️⃣",
+            Value::Five => "5️⃣",
+            Value::Six => "6️⃣",
+            Value::Seven => "7️⃣",
+            Value::Eight => "8️⃣",
+            Value::Nine => "9️⃣",
+            Value::Ten => "🔟",
+            Value::Jack => "🇯",
+            Value::Queen => "🇶",
+            Value::King => "🇰",
+            Value::Hidden => "❓",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
             Value::Ace => "A",
             Value::Two => "2",
             Value::Three => "3",
@@ -176,8 +327,47 @@ impl ToString for Value {
             Value::Queen => "Q",
             Value::King => "K",
             Value::Hidden => "X",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// カードの表示形式
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CardStyle {
+    // "SA"のような記録と同じ形式
+    #[default]
+    Code,
+    // "♠A"のようにスートを記号で表す
+    Symbol,
+    // トランプの絵文字
+    Unicode,
+    // "♠️🇦"のような絵文字
+    Emoji,
+}
+
+impl FromStr for CardStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "code" => Ok(CardStyle::Code),
+            "symbol" => Ok(CardStyle::Symbol),
+            "unicode" => Ok(CardStyle::Unicode),
+            "emoji" => Ok(CardStyle::Emoji),
+            _ => Err("Invalid card style".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for CardStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CardStyle::Code => write!(f, "code"),
+            CardStyle::Symbol => write!(f, "symbol"),
+            CardStyle::Unicode => write!(f, "unicode"),
+            CardStyle::Emoji => write!(f, "emoji"),
         }
-        .to_string()
     }
 }
 
@@ -195,5 +385,44 @@ mod tests {
         let card = Card::from_str("H10").unwrap();
         assert_eq!(card.suit, Suit::Heart);
         assert_eq!(card.value, Value::Ten);
+
+        assert_eq!(Card::from_str("♠A").unwrap(), Card::from_str("SA").unwrap());
+        assert_eq!(Card::from_str("🂡").unwrap(), Card::from_str("SA").unwrap());
+        assert_eq!(
+            Card::from_str("♥️🔟").unwrap(),
+            Card::from_str("H10").unwrap()
+        );
+        assert!(Card::from_str("🂬").is_err());
+    }
+
+    #[test]
+    fn test_card_style() {
+        let styles = [
+            CardStyle::Code,
+            CardStyle::Symbol,
+            CardStyle::Unicode,
+            CardStyle::Emoji,
+        ];
+        let mut cards = vec![Card::new_hidden()];
+        for suit in Suit::iter().filter(|suit| *suit != Suit::Hidden) {
+            for value in Value::iter().filter(|value| *value != Value::Hidden) {
+                cards.push(Card::new(suit, value));
+            }
+        }
+
+        // どの形式で表示しても元のカードに戻せる
+        for style in styles {
+            for card in &cards {
+                assert_eq!(Card::from_str(&card.format(style)).unwrap(), *card);
+            }
+        }
+        assert_eq!(
+            Card::from_str("DQ").unwrap().format(CardStyle::Symbol),
+            "♦Q"
+        );
+        assert_eq!(
+            Card::from_str("CK").unwrap().format(CardStyle::Unicode),
+            "🃞"
+        );
     }
 }
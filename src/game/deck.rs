@@ -1,4 +1,3 @@
-use rand::{seq::SliceRandom, thread_rng};
 use strum::IntoEnumIterator;
 
 use super::card::{Card, Suit, Value};
@@ -26,11 +25,7 @@ impl Deck {
         Deck { cards }
     }
 
-    pub fn shuffle(&mut self) {
-        self.cards.shuffle(&mut thread_rng());
-    }
-
-    pub fn draw(&mut self) -> Option<Card> {
-        self.cards.pop()
+    pub fn into_cards(self) -> Vec<Card> {
+        self.cards
     }
 }
@@ -0,0 +1,65 @@
+use serde_json::Value;
+
+use super::state::{Effect, State};
+
+// 保存するエフェクトの形式のバージョン
+// Effectの形を変えたときはバージョンを上げ、古い形式の読み込みをdecodeに追加する
+pub const EFFECT_VERSION: i32 = 1;
+
+pub fn encode(effect: &Effect) -> Value {
+    serde_json::to_value(effect).unwrap()
+}
+
+pub fn decode(version: i32, value: Value) -> Result<Effect, String> {
+    match version {
+        1 => serde_json::from_value(value).map_err(|e| e.to_string()),
+        _ => Err(format!("Unknown effect version: {}", version)),
+    }
+}
+
+// 記録されたエフェクトを順に適用して状態を再現する
+pub fn replay(effects: impl IntoIterator<Item = Effect>) -> State {
+    let mut state = State::new();
+    for effect in effects {
+        state.apply_effect(effect);
+    }
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::card::Card;
+    use std::{collections::HashMap, str::FromStr};
+
+    fn card(s: &str) -> Card {
+        Card::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let effects = [
+            Effect::Init(vec!["a".to_string()]),
+            Effect::Bet("a".to_string(), 10),
+            Effect::Start,
+            Effect::Deal(
+                HashMap::from([("a".to_string(), (card("S10"), card("HA")))]),
+                (card("D5"), Card::new_hidden()),
+            ),
+            Effect::NextPlayer,
+        ];
+
+        for effect in effects.iter() {
+            let value = encode(effect);
+            assert_eq!(&decode(EFFECT_VERSION, value).unwrap(), effect);
+        }
+        assert_eq!(encode(&effects[1]).to_string(), r#"{"Bet":["a",10]}"#);
+        assert!(decode(EFFECT_VERSION + 1, encode(&effects[0])).is_err());
+
+        let state = replay(effects);
+        assert_eq!(
+            state.get_player("a").unwrap().get_hand(0).get_card_count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use std::str::FromStr;
+
+use sha2::{Digest, Sha256};
+
+use super::{card::Card, shoe::Shoe};
+
+// サーバーシードのハッシュ。ラウンドの前に公開する
+pub fn commitment(server_seed: &str) -> String {
+    hex::encode(Sha256::digest(server_seed.as_bytes()))
+}
+
+// サーバーシードとクライアントシードからシャッフルに使うシードを作る
+pub fn round_seed(server_seed: &str, client_seed: &str) -> u64 {
+    let hash = Sha256::digest(format!("{}:{}", server_seed, client_seed).as_bytes());
+    u64::from_be_bytes(hash[..8].try_into().unwrap())
+}
+
+// ラウンドの途中でシューが尽きたときのシャッフルに使うシード。countは何回目のシャッフルか
+pub fn reshuffle_seed(server_seed: &str, client_seed: &str, count: u32) -> u64 {
+    let hash = Sha256::digest(format!("{}:{}:{}", server_seed, client_seed, count).as_bytes());
+    u64::from_be_bytes(hash[..8].try_into().unwrap())
+}
+
+fn shuffled(shoe: &str, seed: u64) -> Result<Vec<String>, String> {
+    let cards = shoe
+        .split_whitespace()
+        .map(Card::from_str)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut shoe = Shoe::from_cards(cards);
+    shoe.shuffle(seed);
+
+    Ok(std::iter::from_fn(|| shoe.draw())
+        .map(|card| card.to_string())
+        .collect())
+}
+
+// 公開されたシードがハッシュと一致するか確かめ、配られる順番のカードを返す。
+// ラウンドの途中でシャッフルし直した場合は、その分も続けて並べる
+pub fn verify(
+    commitment: &str,
+    server_seed: &str,
+    client_seed: &str,
+    shoe: &str,
+    reshuffles: &[String],
+) -> Result<Vec<String>, String> {
+    if self::commitment(server_seed) != commitment {
+        return Err("The server seed does not match the commitment".to_string());
+    }
+
+    let mut order = shuffled(shoe, round_seed(server_seed, client_seed))?;
+    for (count, shoe) in (1..).zip(reshuffles) {
+        order.extend(shuffled(
+            shoe,
+            reshuffle_seed(server_seed, client_seed, count),
+        )?);
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        let server_seed = "server";
+        let commitment = commitment(server_seed);
+
+        let order = verify(&commitment, server_seed, "client", "SA H10 D5", &[]).unwrap();
+        assert_eq!(order.len(), 3);
+        assert_eq!(
+            order,
+            verify(&commitment, server_seed, "client", "D5 SA H10", &[]).unwrap()
+        );
+
+        // シャッフルし直したカードは後ろに続く
+        let reshuffles = ["C2 C3".to_string()];
+        let order = verify(&commitment, server_seed, "client", "SA H10 D5", &reshuffles).unwrap();
+        assert_eq!(order.len(), 5);
+        assert!(order[3..].contains(&"C2".to_string()));
+
+        assert!(verify(&commitment, "tampered", "client", "SA H10 D5", &[]).is_err());
+    }
+}
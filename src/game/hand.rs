@@ -0,0 +1,169 @@
+use super::card::{Card, CardStyle};
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub struct Hand {
+    cards: Vec<Card>,
+    amount: u32,
+    split: bool,
+    surrendered: bool,
+}
+
+impl Hand {
+    pub fn new() -> Hand {
+        Hand {
+            cards: Vec::new(),
+            amount: 0,
+            split: false,
+            surrendered: false,
+        }
+    }
+
+    pub fn bet(&mut self, amount: u32) {
+        self.amount += amount;
+    }
+
+    pub fn add_card(&mut self, card: Card) {
+        self.cards.push(card);
+    }
+
+    pub fn replace_card(&mut self, index: usize, card: Card) {
+        self.cards[index] = card;
+    }
+
+    pub fn get_cards(&self) -> Vec<Card> {
+        self.cards.clone()
+    }
+
+    pub fn get_card_count(&self) -> usize {
+        self.cards.len()
+    }
+
+    pub fn get_amount(&self) -> u32 {
+        self.amount
+    }
+
+    // スプリットで作られたハンドかどうか
+    pub fn is_split(&self) -> bool {
+        self.split
+    }
+
+    pub fn surrender(&mut self) {
+        self.surrendered = true;
+    }
+
+    pub fn is_surrendered(&self) -> bool {
+        self.surrendered
+    }
+
+    pub fn is_pair(&self) -> bool {
+        self.cards.len() == 2 && self.cards[0].get_score(true) == self.cards[1].get_score(true)
+    }
+
+    pub fn is_ace_pair(&self) -> bool {
+        self.is_pair() && self.cards[0].is_ace()
+    }
+
+    // 2枚目のカードを新しいハンドに移し、同額をベットする
+    pub fn split(&mut self, amount: u32) -> Hand {
+        self.split = true;
+        let card = self.cards.pop().unwrap();
+        Hand {
+            cards: vec![card],
+            amount,
+            split: true,
+            surrendered: false,
+        }
+    }
+
+    pub fn value(&self) -> HandValue {
+        HandValue::new(&self.cards)
+    }
+}
+
+// ハンドの評価。ソフトはエースを11として数えていることを表す
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HandValue {
+    pub total: u32,
+    pub soft: bool,
+    pub is_blackjack: bool,
+    pub is_bust: bool,
+}
+
+impl HandValue {
+    pub fn new(cards: &[Card]) -> HandValue {
+        let mut total: u32 = cards.iter().map(|card| card.get_score(false)).sum();
+        let has_ace = cards.iter().any(|card| card.is_ace());
+
+        // エースは1枚だけ11として数えられる
+        let soft = has_ace && total + Card::ace_diff() <= 21;
+        if soft {
+            total += Card::ace_diff();
+        }
+
+        HandValue {
+            total,
+            soft,
+            is_blackjack: cards.len() == 2 && total == 21,
+            is_bust: total > 21,
+        }
+    }
+}
+
+impl fmt::Display for HandValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.soft && self.total < 21 {
+            write!(f, "{}/{}", self.total - Card::ace_diff(), self.total)
+        } else {
+            write!(f, "{}", self.total)
+        }
+    }
+}
+
+impl Hand {
+    pub fn format(&self, style: CardStyle) -> String {
+        let mut s = String::new();
+        for card in &self.cards {
+            s.push_str(&card.format(style));
+            s.push(' ');
+        }
+        s.push_str(&format!("({})", self.value()));
+        s
+    }
+}
+
+impl fmt::Display for Hand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format(CardStyle::Code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn hand_value(cards: &[&str]) -> HandValue {
+        let cards: Vec<Card> = cards.iter().map(|s| Card::from_str(s).unwrap()).collect();
+        HandValue::new(&cards)
+    }
+
+    #[test]
+    fn test_hand_value() {
+        let value = hand_value(&["SA", "H6"]);
+        assert_eq!(value.total, 17);
+        assert!(value.soft);
+        assert_eq!(value.to_string(), "7/17");
+
+        let value = hand_value(&["SA", "H6", "D10"]);
+        assert_eq!(value.total, 17);
+        assert!(!value.soft);
+
+        let value = hand_value(&["SA", "HK"]);
+        assert!(value.is_blackjack);
+        assert_eq!(value.to_string(), "21");
+
+        let value = hand_value(&["S10", "H6", "DK"]);
+        assert!(value.is_bust);
+    }
+}
@@ -0,0 +1,246 @@
+use std::{collections::HashMap, str::FromStr};
+
+use super::{
+    card::Card,
+    event,
+    rules::TableRules,
+    state::{Effect, State},
+};
+
+// 保存用のハンド。カードは空白区切りの文字列で持つ
+pub struct HandRecord {
+    pub cards: String,
+    pub amount: u32,
+    pub split: bool,
+    pub surrendered: bool,
+}
+
+pub struct PlayerRecord {
+    pub name: String,
+    pub insurance: u32,
+    pub even_money: bool,
+    pub payout: u32,
+    pub net: i32,
+    pub hands: Vec<HandRecord>,
+}
+
+// 終了したラウンドの記録
+pub struct RoundRecord {
+    pub dealer_cards: String,
+    pub players: Vec<PlayerRecord>,
+}
+
+fn format_cards(cards: &[Card]) -> String {
+    cards
+        .iter()
+        .map(|card| card.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn parse_cards(cards: &str) -> Result<Vec<Card>, String> {
+    cards.split_whitespace().map(Card::from_str).collect()
+}
+
+impl RoundRecord {
+    // 終了したラウンドの状態から記録を作る
+    pub fn new(state: &State, rules: &TableRules) -> RoundRecord {
+        let result = state.get_result(rules);
+        let players = state
+            .get_player_order()
+            .into_iter()
+            .map(|name| {
+                let player = state.get_player(&name).unwrap();
+                let (payout, net) = result[&name];
+                PlayerRecord {
+                    insurance: player.get_insurance(),
+                    even_money: player.has_even_money(),
+                    payout,
+                    net,
+                    hands: (0..player.get_hand_count())
+                        .map(|index| {
+                            let hand = player.get_hand(index);
+                            HandRecord {
+                                cards: format_cards(&hand.get_cards()),
+                                amount: hand.get_amount(),
+                                split: hand.is_split(),
+                                surrendered: hand.is_surrendered(),
+                            }
+                        })
+                        .collect(),
+                    name,
+                }
+            })
+            .collect();
+
+        RoundRecord {
+            dealer_cards: format_cards(&state.get_dealer_hands(false)),
+            players,
+        }
+    }
+
+    // 記録したラウンドの状態を再現するエフェクトを作る
+    // スプリットは次のハンドの最初のカードを加えてから行い、そのカードを新しいハンドに移す
+    pub fn to_effects(&self) -> Result<Vec<Effect>, String> {
+        let mut effects = vec![
+            Effect::Init(
+                self.players
+                    .iter()
+                    .map(|player| player.name.clone())
+                    .collect(),
+            ),
+            Effect::Start,
+        ];
+
+        for card in parse_cards(&self.dealer_cards)? {
+            effects.push(Effect::AddDealerCard(card));
+        }
+        for player in &self.players {
+            if player.insurance > 0 {
+                effects.push(Effect::Insurance(player.name.clone(), player.insurance));
+            }
+            if player.even_money {
+                effects.push(Effect::EvenMoney(player.name.clone()));
+            }
+        }
+
+        effects.push(Effect::NextPlayer);
+        for player in &self.players {
+            let hands = &player.hands;
+            for (index, hand) in hands.iter().enumerate() {
+                let mut cards = parse_cards(&hand.cards)?;
+                // スプリットで作られたハンドは最初のカードを既に持っている
+                if index > 0 && !cards.is_empty() {
+                    cards.remove(0);
+                }
+                if let Some(next) = hands.get(index + 1) {
+                    let card = parse_cards(&next.cards)?
+                        .first()
+                        .copied()
+                        .ok_or("A split hand has no cards".to_string())?;
+                    effects.push(Effect::AddCard(player.name.clone(), card));
+                    effects.push(Effect::Split(player.name.clone(), 0));
+                }
+                for card in cards {
+                    effects.push(Effect::AddCard(player.name.clone(), card));
+                }
+                effects.push(Effect::Bet(player.name.clone(), hand.amount));
+                if hand.surrendered {
+                    effects.push(Effect::Surrender(player.name.clone()));
+                }
+                effects.push(Effect::NextPlayer);
+            }
+        }
+        effects.push(Effect::Finish);
+
+        Ok(effects)
+    }
+
+    // 記録からラウンドの状態を再現する
+    pub fn replay(&self) -> Result<State, String> {
+        Ok(event::replay(self.to_effects()?))
+    }
+}
+
+// 記録されたエフェクトを順に適用し、Finishまで進んだラウンドの記録をコミットメントごとに返す
+pub fn finished_rounds(
+    effects: impl IntoIterator<Item = Effect>,
+    rules: &TableRules,
+) -> HashMap<String, RoundRecord> {
+    let mut state = State::new();
+    let mut commitment = None;
+    let mut rounds = HashMap::new();
+    for effect in effects {
+        if let Effect::Commit(hash) = &effect {
+            commitment = Some(hash.clone());
+        }
+        let finished = effect == Effect::Finish;
+        state.apply_effect(effect);
+
+        if finished {
+            if let Some(commitment) = commitment.take() {
+                rounds.insert(commitment, RoundRecord::new(&state, rules));
+            }
+        }
+    }
+    rounds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(s: &str) -> Card {
+        Card::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_replay() {
+        let rules = TableRules::default();
+        let mut state = State::new();
+        let effects = vec![
+            Effect::Init(vec!["a".to_string(), "b".to_string()]),
+            Effect::Bet("a".to_string(), 10),
+            Effect::Bet("b".to_string(), 20),
+            Effect::Start,
+            Effect::Deal(
+                HashMap::from([
+                    ("a".to_string(), (card("S8"), card("H8"))),
+                    ("b".to_string(), (card("SK"), card("H6"))),
+                ]),
+                (card("S10"), card("C7")),
+            ),
+            Effect::NextPlayer,
+            Effect::Split("a".to_string(), 10),
+            Effect::AddCard("a".to_string(), card("D3")),
+            Effect::DoubleDown("a".to_string(), 10),
+            Effect::AddCard("a".to_string(), card("C9")),
+            Effect::NextPlayer,
+            Effect::AddCard("a".to_string(), card("C10")),
+            Effect::NextPlayer,
+            Effect::Surrender("b".to_string()),
+            Effect::NextPlayer,
+            Effect::Finish,
+        ];
+        for effect in effects {
+            state.apply_effect(effect);
+        }
+
+        let record = RoundRecord::new(&state, &rules);
+        assert_eq!(record.players[0].hands.len(), 2);
+        assert_eq!(record.players[0].hands[0].cards, "S8 D3 C9");
+
+        let replayed = record.replay().unwrap();
+        assert_eq!(replayed.to_string(), state.to_string());
+        assert_eq!(replayed.get_result(&rules), state.get_result(&rules));
+    }
+
+    #[test]
+    fn test_finished_rounds() {
+        let round = |commitment: &str, finished: bool| {
+            let mut effects = vec![
+                Effect::Init(vec!["a".to_string()]),
+                Effect::Commit(commitment.to_string()),
+                Effect::Bet("a".to_string(), 10),
+                Effect::Start,
+                Effect::Deal(
+                    HashMap::from([("a".to_string(), (card("S10"), card("H9")))]),
+                    (card("D10"), card("C7")),
+                ),
+                Effect::NextPlayer,
+                Effect::NextPlayer,
+            ];
+            if finished {
+                effects.push(Effect::Finish);
+            }
+            effects
+        };
+        let effects = [round("first", true), round("second", false)].concat();
+
+        // 中断したラウンドは含めない
+        let rounds = finished_rounds(effects, &TableRules::default());
+        assert_eq!(rounds.len(), 1);
+        assert_eq!(rounds["first"].players[0].payout, 20);
+        assert_eq!(rounds["first"].players[0].net, 10);
+    }
+}
@@ -1,80 +1,125 @@
-use super::card::Card;
+use super::{
+    card::{Card, CardStyle},
+    hand::{Hand, HandValue},
+};
 use std::fmt;
 
 #[derive(Debug, Clone)]
 pub struct Player {
     pub name: String,
-    hands: Vec<Card>,
-    amount: u32,
+    hands: Vec<Hand>,
+    insurance: u32,
+    even_money: bool,
+    // ベットを終えてすぐに始めてよいか
+    ready: bool,
 }
 
 impl Player {
     pub fn new(name: String) -> Player {
         Player {
             name,
-            hands: Vec::new(),
-            amount: 0,
+            hands: vec![Hand::new()],
+            insurance: 0,
+            even_money: false,
+            ready: false,
         }
     }
 
     pub fn clear(&mut self) {
-        self.hands.clear();
-        self.amount = 0;
+        self.hands = vec![Hand::new()];
+        self.insurance = 0;
+        self.even_money = false;
+        self.ready = false;
     }
 
-    pub fn bet(&mut self, amount: u32) {
-        self.amount += amount;
+    pub fn ready(&mut self) {
+        self.ready = true;
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    pub fn insure(&mut self, amount: u32) {
+        self.insurance = amount;
+    }
+
+    pub fn take_even_money(&mut self) {
+        self.even_money = true;
+    }
+
+    pub fn get_insurance(&self) -> u32 {
+        self.insurance
+    }
+
+    pub fn has_even_money(&self) -> bool {
+        self.even_money
+    }
+
+    pub fn bet(&mut self, index: usize, amount: u32) {
+        self.hands[index].bet(amount);
     }
 
     pub fn get_hands(&self, hide: bool) -> Vec<Card> {
+        let mut hands = self.hands[0].get_cards();
         if hide {
-            let mut hands = self.hands.clone();
             hands[1] = Card::new_hidden();
-            hands
-        } else {
-            self.hands.clone()
         }
+        hands
     }
 
-    pub fn add_card(&mut self, card: Card) {
-        self.hands.push(card);
+    pub fn get_hand(&self, index: usize) -> &Hand {
+        &self.hands[index]
+    }
+
+    pub fn get_hand_count(&self) -> usize {
+        self.hands.len()
+    }
+
+    pub fn add_card(&mut self, index: usize, card: Card) {
+        self.hands[index].add_card(card);
     }
 
     pub fn open_card(&mut self, card: Card) {
-        self.hands[1] = card;
-    }
-
-    pub fn get_score(&self) -> u32 {
-        let mut score = 0;
-        let mut ace_count = 0;
-        for card in &self.hands {
-            let card_score = card.get_score(true);
-            if card.is_ace() {
-                ace_count += 1;
-            }
-            score += card_score;
-        }
+        self.hands[0].replace_card(1, card);
+    }
 
-        let ace_diff = Card::ace_diff();
-        while score > 21 && ace_count > 0 {
-            score -= ace_diff;
-            ace_count -= 1;
-        }
+    pub fn surrender(&mut self, index: usize) {
+        self.hands[index].surrender();
+    }
+
+    pub fn split(&mut self, index: usize, amount: u32) {
+        let hand = self.hands[index].split(amount);
+        self.hands.insert(index + 1, hand);
+    }
 
-        score
+    // スプリットしていない最初の2枚で21
+    pub fn is_natural(&self) -> bool {
+        self.hands.len() == 1 && !self.hands[0].is_split() && self.hands[0].value().is_blackjack
+    }
+
+    pub fn value(&self) -> HandValue {
+        self.hands[0].value()
     }
 
     pub fn get_amount(&self) -> u32 {
-        self.amount
+        self.hands.iter().map(|hand| hand.get_amount()).sum()
+    }
+}
+
+impl Player {
+    pub fn format(&self, style: CardStyle) -> String {
+        let hands = self
+            .hands
+            .iter()
+            .map(|hand| hand.format(style))
+            .collect::<Vec<_>>();
+        format!("{}: {}", self.name, hands.join(" / "))
     }
 }
 
 impl fmt::Display for Player {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}:", self.name)?;
-        for card in &self.hands {
-            write!(f, " {}", card)?;
-        }
-        write!(f, " ({})", self.get_score())
+        write!(f, "{}", self.format(CardStyle::Code))
     }
 }
@@ -0,0 +1,209 @@
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Surrender {
+    Disabled,
+    // ディーラーがブラックジャックを確認した後のみ
+    Late,
+    // ディーラーがブラックジャックを確認する前でも可能
+    Early,
+}
+
+impl FromStr for Surrender {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Surrender::Disabled),
+            "late" => Ok(Surrender::Late),
+            "early" => Ok(Surrender::Early),
+            _ => Err("Invalid surrender rule".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Surrender {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Surrender::Disabled => write!(f, "none"),
+            Surrender::Late => write!(f, "late"),
+            Surrender::Early => write!(f, "early"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlackjackPayout {
+    ThreeToTwo,
+    SixToFive,
+}
+
+impl BlackjackPayout {
+    // 掛け金を含めた払い戻し額
+    pub fn payout(&self, amount: u32) -> u32 {
+        match self {
+            BlackjackPayout::ThreeToTwo => amount + amount * 3 / 2,
+            BlackjackPayout::SixToFive => amount + amount * 6 / 5,
+        }
+    }
+}
+
+impl FromStr for BlackjackPayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "3:2" => Ok(BlackjackPayout::ThreeToTwo),
+            "6:5" => Ok(BlackjackPayout::SixToFive),
+            _ => Err("Invalid blackjack payout".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for BlackjackPayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlackjackPayout::ThreeToTwo => write!(f, "3:2"),
+            BlackjackPayout::SixToFive => write!(f, "6:5"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TableRules {
+    // シューに入れるデッキの数
+    pub decks: u32,
+    // カットカードまでに配るカードの割合（%）
+    pub penetration: u32,
+    // シャッフル後に1枚捨てるか
+    pub burn_card: bool,
+    // ディーラーがソフト17でヒットするか
+    pub dealer_hits_soft_17: bool,
+    pub blackjack_payout: BlackjackPayout,
+    // スプリット後のダブルダウンを認めるか
+    pub double_after_split: bool,
+    // スプリットしたハンドを再度スプリットできるか
+    pub resplit: bool,
+    // スプリットで作れるハンドの最大数
+    pub max_split_hands: usize,
+    // エースをスプリットした場合は1枚しか配らない
+    pub split_aces_one_card: bool,
+    pub surrender: Surrender,
+    pub min_bet: u32,
+    pub max_bet: u32,
+    // ベットの受付時間（秒）
+    pub betting_time: u64,
+    // 1ターンの制限時間（秒）
+    pub turn_timeout: u64,
+}
+
+impl TableRules {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=8).contains(&self.decks) {
+            return Err("The number of decks must be between 1 and 8".to_string());
+        }
+        if !(10..=90).contains(&self.penetration) {
+            return Err("Penetration must be between 10 and 90 percent".to_string());
+        }
+        if self.max_split_hands < 2 {
+            return Err("At least two split hands must be allowed".to_string());
+        }
+        if self.min_bet == 0 || self.min_bet > self.max_bet {
+            return Err("Invalid bet limits".to_string());
+        }
+        if self.betting_time == 0 || self.turn_timeout == 0 {
+            return Err("Time limits must be positive".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for TableRules {
+    fn default() -> Self {
+        TableRules {
+            decks: 1,
+            penetration: 75,
+            burn_card: true,
+            dealer_hits_soft_17: false,
+            blackjack_payout: BlackjackPayout::ThreeToTwo,
+            double_after_split: true,
+            resplit: true,
+            max_split_hands: 4,
+            split_aces_one_card: true,
+            surrender: Surrender::Late,
+            min_bet: 1,
+            max_bet: 10000,
+            betting_time: 15,
+            turn_timeout: 60,
+        }
+    }
+}
+
+impl fmt::Display for TableRules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "デッキ数: {}", self.decks)?;
+        writeln!(f, "ペネトレーション: {}%", self.penetration)?;
+        writeln!(
+            f,
+            "バーンカード: {}",
+            if self.burn_card { "あり" } else { "なし" }
+        )?;
+        writeln!(
+            f,
+            "ディーラー: {}",
+            if self.dealer_hits_soft_17 {
+                "H17"
+            } else {
+                "S17"
+            }
+        )?;
+        writeln!(f, "ブラックジャック配当: {}", self.blackjack_payout)?;
+        writeln!(
+            f,
+            "スプリット後のダブルダウン: {}",
+            if self.double_after_split {
+                "可"
+            } else {
+                "不可"
+            }
+        )?;
+        writeln!(
+            f,
+            "リスプリット: {} (最大{}ハンド)",
+            if self.resplit { "可" } else { "不可" },
+            self.max_split_hands
+        )?;
+        writeln!(
+            f,
+            "エースのスプリット: {}",
+            if self.split_aces_one_card {
+                "1枚のみ"
+            } else {
+                "制限なし"
+            }
+        )?;
+        writeln!(f, "サレンダー: {}", self.surrender)?;
+        writeln!(
+            f,
+            "サレンダーの返金とインシュランス: 掛け金の半額（端数は切り捨て）"
+        )?;
+        writeln!(f, "ベット: {}〜{}", self.min_bet, self.max_bet)?;
+        writeln!(f, "ベット受付時間: {}秒", self.betting_time)?;
+        write!(f, "ターンの制限時間: {}秒", self.turn_timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blackjack_payout() {
+        let payout = BlackjackPayout::from_str("3:2").unwrap();
+        assert_eq!(payout.payout(10), 25);
+
+        let payout = BlackjackPayout::from_str("6:5").unwrap();
+        assert_eq!(payout.payout(10), 22);
+    }
+}
@@ -0,0 +1,106 @@
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use super::{card::Card, deck::Deck};
+
+// 複数のデッキをまとめたシュー。ラウンドをまたいで使い続ける
+pub struct Shoe {
+    cards: Vec<Card>,
+    decks: u32,
+    penetration: u32,
+    burn_card: bool,
+    cut_card: usize,
+    // テスト用に並び順を固定する
+    stacked: bool,
+}
+
+impl Shoe {
+    pub fn new(decks: u32, penetration: u32, burn_card: bool) -> Shoe {
+        Shoe {
+            cards: Vec::new(),
+            decks,
+            penetration,
+            burn_card,
+            cut_card: 0,
+            stacked: false,
+        }
+    }
+
+    // 検証用に残りのカードからシューを作る
+    pub fn from_cards(cards: Vec<Card>) -> Shoe {
+        let mut shoe = Shoe::new(1, 100, false);
+        shoe.cards = cards;
+        shoe
+    }
+
+    // 渡した順番のままカードを配るシュー
+    #[cfg(test)]
+    pub fn stacked(cards: Vec<Card>) -> Shoe {
+        Shoe {
+            cards: cards.into_iter().rev().collect(),
+            decks: 1,
+            penetration: 100,
+            burn_card: false,
+            cut_card: 0,
+            stacked: true,
+        }
+    }
+
+    // すべてのカードをシューに戻し、カットカードを差し込む
+    pub fn refill(&mut self) {
+        self.cards.clear();
+        for _ in 0..self.decks {
+            self.cards.append(&mut Deck::new().into_cards());
+        }
+
+        // カットカードより後ろのカードは配らない
+        let total = self.cards.len();
+        self.cut_card = total - total * self.penetration as usize / 100;
+    }
+
+    // ラウンドの途中でシューが尽きたときに、場に出ているカードを除いてシューに戻す。
+    // 場のカードは戻っていないので、次のラウンドの前にすべて戻す
+    pub fn refill_except(&mut self, in_play: &[Card]) {
+        self.refill();
+        for card in in_play {
+            if let Some(index) = self.cards.iter().position(|c| c == card) {
+                self.cards.swap_remove(index);
+            }
+        }
+        self.cut_card = self.cards.len();
+    }
+
+    // 残りのカードを整列してからシードでシャッフルする。
+    // 同じ残りのカードとシードからは必ず同じ並びになる
+    pub fn shuffle(&mut self, seed: u64) {
+        if self.stacked {
+            return;
+        }
+
+        self.cards.sort();
+        self.cards.shuffle(&mut StdRng::seed_from_u64(seed));
+    }
+
+    // 捨てたカードを返す。バーンカードを使わないルールではNone
+    pub fn burn(&mut self) -> Option<Card> {
+        if self.burn_card {
+            self.cards.pop()
+        } else {
+            None
+        }
+    }
+
+    pub fn needs_shuffle(&self) -> bool {
+        self.cards.len() <= self.cut_card
+    }
+
+    // 残りのカードを整列して返す
+    pub fn get_cards(&self) -> Vec<Card> {
+        let mut cards = self.cards.clone();
+        cards.sort();
+        cards
+    }
+
+    pub fn draw(&mut self) -> Option<Card> {
+        self.cards.pop()
+    }
+}
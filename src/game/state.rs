@@ -1,6 +1,14 @@
 use std::{collections::HashMap, fmt};
 
-use super::{card::Card, player::Player, status::Status};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    card::{Card, CardStyle},
+    hand::{Hand, HandValue},
+    player::Player,
+    rules::TableRules,
+    status::Status,
+};
 
 pub struct State {
     players: HashMap<String, Player>,
@@ -9,22 +17,49 @@ pub struct State {
     status: Status,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Effect {
     Init(Vec<String>),
+    Shuffle,
+    Commit(String),
+    ClientSeed(String, String),
+    RoundSeed(u64, Vec<Card>),
+    // シャッフルした後に最初の1枚を捨てたカード
+    Burn(Card),
+    // ラウンドの途中でシャッフルし直したときのシードと、シューに戻したカード
+    Reshuffle(u64, Vec<Card>),
+    Reveal(String, String),
     AddPlayer(String),
     RemovePlayer(String),
     Bet(String, u32),
+    Ready(String),
+    ExtendBetting(u64),
+    Refund(String, u32),
+    SitOut(String, u32),
     Deal(HashMap<String, (Card, Card)>, (Card, Card)),
     DealerBlackjack,
+    OfferInsurance,
+    OfferSurrender,
+    Insurance(String, u32),
+    EvenMoney(String),
+    CloseInsurance,
     Start,
     AddCard(String, Card),
+    DoubleDown(String, u32),
+    Split(String, u32),
+    Surrender(String),
     AddDealerCard(Card),
     OpenDealerCard(Card),
     Burst(String),
     DealerBurst,
     NextPlayer,
+    TurnWarning(String, u64),
+    TimedOut(String, u32),
     NoPlayer,
+    Pause,
+    Resume,
+    Stop,
+    Close,
     Finish,
 }
 
@@ -49,19 +84,44 @@ impl State {
                     self.add_player(player);
                 }
             }
+            Effect::Shuffle => {}
+            Effect::Commit(_) => {}
+            Effect::ClientSeed(_, _) => {}
+            Effect::RoundSeed(_, _) => {}
+            Effect::Burn(_) => {}
+            Effect::Reshuffle(_, _) => {}
+            Effect::Reveal(_, _) => {}
             Effect::AddPlayer(name) => self.add_player(name),
             Effect::RemovePlayer(name) => self.remove_player(&name),
             Effect::Bet(name, amount) => self.bet(&name, amount),
+            Effect::Ready(name) => self.players.get_mut(&name).unwrap().ready(),
+            Effect::ExtendBetting(_) => {}
+            Effect::Refund(_, _) => {}
+            Effect::SitOut(name, _) => self.remove_player(&name),
             Effect::Deal(player_cards, dealer_cards) => self.deal(player_cards, dealer_cards),
             Effect::DealerBlackjack => {}
+            Effect::OfferInsurance => self.status = Status::Insurance,
+            Effect::OfferSurrender => self.status = Status::Insurance,
+            Effect::Insurance(name, amount) => self.insure(&name, amount),
+            Effect::EvenMoney(name) => self.take_even_money(&name),
+            Effect::CloseInsurance => self.status = Status::Dealing,
             Effect::Start => self.start(),
             Effect::AddCard(name, card) => self.add_card(&name, card),
+            Effect::DoubleDown(name, amount) => self.bet(&name, amount),
+            Effect::Split(name, amount) => self.split(&name, amount),
+            Effect::Surrender(name) => self.surrender(&name),
             Effect::AddDealerCard(card) => self.add_dealer_card(card),
             Effect::OpenDealerCard(card) => self.open_dealer_card(card),
             Effect::Burst(_) => {}
             Effect::DealerBurst => {}
             Effect::NextPlayer => self.next_player(),
+            Effect::TurnWarning(_, _) => {}
+            Effect::TimedOut(_, _) => {}
             Effect::NoPlayer => self.finish(),
+            Effect::Pause => {}
+            Effect::Resume => {}
+            Effect::Stop => {}
+            Effect::Close => {}
             Effect::Finish => self.finish(),
         }
     }
@@ -77,7 +137,26 @@ impl State {
     }
 
     fn bet(&mut self, name: &str, amount: u32) {
-        self.players.get_mut(name).unwrap().bet(amount);
+        let index = self.get_hand_index(name);
+        self.players.get_mut(name).unwrap().bet(index, amount);
+    }
+
+    fn insure(&mut self, name: &str, amount: u32) {
+        self.players.get_mut(name).unwrap().insure(amount);
+    }
+
+    fn take_even_money(&mut self, name: &str) {
+        self.players.get_mut(name).unwrap().take_even_money();
+    }
+
+    fn surrender(&mut self, name: &str) {
+        let index = self.get_hand_index(name);
+        self.players.get_mut(name).unwrap().surrender(index);
+    }
+
+    fn split(&mut self, name: &str, amount: u32) {
+        let index = self.get_hand_index(name);
+        self.players.get_mut(name).unwrap().split(index, amount);
     }
 
     fn deal(&mut self, player_cards: HashMap<String, (Card, Card)>, dealer_cards: (Card, Card)) {
@@ -89,16 +168,25 @@ impl State {
         self.add_dealer_card(dealer_cards.1);
     }
 
+    // プレイ中のプレイヤーであれば現在のハンド、それ以外は最初のハンドの番号を返す
+    fn get_hand_index(&self, name: &str) -> usize {
+        match self.status {
+            Status::Playing(i, hand) if self.player_order[i] == name => hand,
+            _ => 0,
+        }
+    }
+
     fn start(&mut self) {
         self.status = Status::Dealing;
     }
 
     fn add_card(&mut self, name: &str, card: Card) {
-        self.players.get_mut(name).unwrap().add_card(card);
+        let index = self.get_hand_index(name);
+        self.players.get_mut(name).unwrap().add_card(index, card);
     }
 
     fn add_dealer_card(&mut self, card: Card) {
-        self.dealer.add_card(card);
+        self.dealer.add_card(0, card);
     }
 
     fn open_dealer_card(&mut self, card: Card) {
@@ -107,15 +195,17 @@ impl State {
 
     fn next_player(&mut self) {
         match self.status {
-            Status::Playing(i) => {
-                if i + 1 < self.player_order.len() {
-                    self.status = Status::Playing(i + 1);
+            Status::Playing(i, hand) => {
+                if hand + 1 < self.players[&self.player_order[i]].get_hand_count() {
+                    self.status = Status::Playing(i, hand + 1);
+                } else if i + 1 < self.player_order.len() {
+                    self.status = Status::Playing(i + 1, 0);
                 } else {
                     self.status = Status::DealerTurn;
                 }
             }
             Status::Dealing => {
-                self.status = Status::Playing(0);
+                self.status = Status::Playing(0, 0);
             }
             _ => {}
         }
@@ -126,13 +216,50 @@ impl State {
     }
 
     pub fn get_current_player(&self) -> Option<&Player> {
-        if let Status::Playing(i) = self.status {
+        if let Status::Playing(i, _) = self.status {
             self.players.get(&self.player_order[i])
         } else {
             None
         }
     }
 
+    pub fn get_current_hand(&self) -> Option<&Hand> {
+        if let Status::Playing(i, hand) = self.status {
+            self.players
+                .get(&self.player_order[i])
+                .map(|player| player.get_hand(hand))
+        } else {
+            None
+        }
+    }
+
+    pub fn get_current_hand_index(&self) -> Option<usize> {
+        if let Status::Playing(_, hand) = self.status {
+            Some(hand)
+        } else {
+            None
+        }
+    }
+
+    // 現在のハンドでダブルダウンできるか。残高は考慮しない
+    pub fn can_double_down(&self, rules: &TableRules) -> bool {
+        self.get_current_hand().is_some_and(|hand| {
+            hand.get_card_count() == 2 && (!hand.is_split() || rules.double_after_split)
+        })
+    }
+
+    // 現在のハンドでスプリットできるか。残高は考慮しない
+    pub fn can_split(&self, rules: &TableRules) -> bool {
+        match (self.get_current_player(), self.get_current_hand()) {
+            (Some(player), Some(hand)) => {
+                player.get_hand_count() < rules.max_split_hands
+                    && hand.is_pair()
+                    && (!hand.is_split() || rules.resplit)
+            }
+            _ => false,
+        }
+    }
+
     pub fn get_player_order(&self) -> Vec<String> {
         self.player_order.clone()
     }
@@ -153,66 +280,112 @@ impl State {
         self.dealer.get_hands(hide)
     }
 
-    pub fn get_dealer_score(&self) -> u32 {
-        self.dealer.get_score()
+    pub fn get_dealer_value(&self) -> HandValue {
+        self.dealer.value()
     }
 
     pub fn is_betting(&self) -> bool {
         self.status == Status::Betting
     }
 
+    // ベットした全てのプレイヤーが準備完了したか。ベットしていないプレイヤーはこのラウンドを見送るので待たない
+    pub fn is_all_ready(&self) -> bool {
+        let mut betting = self
+            .players
+            .values()
+            .filter(|player| player.get_amount() > 0)
+            .peekable();
+        betting.peek().is_some() && betting.all(|player| player.is_ready())
+    }
+
+    pub fn is_insurance(&self) -> bool {
+        self.status == Status::Insurance
+    }
+
     pub fn is_dealer_turn(&self) -> bool {
         self.status == Status::DealerTurn
     }
 
-    pub fn has_player(&self, name: &str) -> bool {
-        self.players.contains_key(name)
+    pub fn is_dealer_blackjack(&self) -> bool {
+        self.dealer.value().is_blackjack
     }
 
-    pub fn is_started(&self) -> bool {
-        self.status != Status::Betting
+    pub fn has_player(&self, name: &str) -> bool {
+        self.players.contains_key(name)
     }
 
     pub fn is_finished(&self) -> bool {
         self.status == Status::End
     }
 
-    pub fn get_result(&self) -> HashMap<String, (u32, i32)> {
+    pub fn get_result(&self, rules: &TableRules) -> HashMap<String, (u32, i32)> {
         let mut result = HashMap::new();
-        let dealer_score = self.dealer.get_score();
+        let dealer_value = self.dealer.value();
+        let dealer_blackjack = self.is_dealer_blackjack();
         for name in &self.player_order {
-            let player_score = self.players[name].get_score();
-            let player_amount = self.players[name].get_amount();
-            let score = if player_score > 21 {
-                0
-            } else if dealer_score > 21 || player_score > dealer_score {
-                2
-            } else if player_score < dealer_score {
-                0
+            let player = &self.players[name];
+            // インシュランスは2:1で支払う
+            let mut payout = if dealer_blackjack {
+                player.get_insurance() * 3
             } else {
-                1
-            } * player_amount;
-            result.insert(name.clone(), (score, score as i32 - player_amount as i32));
+                0
+            };
+            // ハンドごとに精算する
+            for index in 0..player.get_hand_count() {
+                let hand = player.get_hand(index);
+                if player.has_even_money() {
+                    payout += 2 * hand.get_amount();
+                    continue;
+                }
+                // サレンダーしたハンドは掛け金の半分を返す。奇数の掛け金の端数は切り捨てて胴元が受け取る
+                if hand.is_surrendered() {
+                    payout += hand.get_amount() / 2;
+                    continue;
+                }
+
+                // ナチュラルはディーラーのナチュラルとのみ引き分ける
+                if player.is_natural() {
+                    payout += if dealer_blackjack {
+                        hand.get_amount()
+                    } else {
+                        rules.blackjack_payout.payout(hand.get_amount())
+                    };
+                    continue;
+                }
+                if dealer_blackjack {
+                    continue;
+                }
+
+                let player_value = hand.value();
+                payout += if player_value.is_bust {
+                    0
+                } else if dealer_value.is_bust || player_value.total > dealer_value.total {
+                    2
+                } else if player_value.total < dealer_value.total {
+                    0
+                } else {
+                    1
+                } * hand.get_amount();
+            }
+            let player_amount = player.get_amount() + player.get_insurance();
+            result.insert(name.clone(), (payout, payout as i32 - player_amount as i32));
         }
         result
     }
+}
 
-    pub fn get_amounts(&self) -> HashMap<String, u32> {
-        let mut amounts = HashMap::new();
+impl State {
+    pub fn format(&self, style: CardStyle) -> String {
+        let mut s = format!("{}\n", self.dealer.format(style));
         for name in &self.player_order {
-            amounts.insert(name.clone(), self.players[name].get_amount());
+            s.push_str(&format!("{}\n", self.players[name].format(style)));
         }
-        amounts
+        s
     }
 }
 
 impl fmt::Display for State {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "{}", self.dealer).and_then(|_| {
-            for name in &self.player_order {
-                writeln!(f, "{}", self.players[name])?;
-            }
-            Ok(())
-        })
+        write!(f, "{}", self.format(CardStyle::Code))
     }
 }
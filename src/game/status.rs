@@ -1,10 +1,12 @@
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Status {
     Betting,
     Dealing,
-    Playing(usize),
+    Insurance,
+    // (席の番号, ハンドの番号)
+    Playing(usize, usize),
     DealerTurn,
     End,
 }
@@ -17,17 +19,23 @@ impl FromStr for Status {
         match s {
             "Betting" => Ok(Status::Betting),
             "Dealing" => Ok(Status::Dealing),
+            "Insurance" => Ok(Status::Insurance),
             "DealerTurn" => Ok(Status::DealerTurn),
             "End" => Ok(Status::End),
             _ => {
                 if s.starts_with("Playing ") {
-                    let index = s
-                        .split_whitespace()
-                        .nth(1)
+                    let mut indices = s.split_whitespace().skip(1);
+                    let index = indices
+                        .next()
                         .ok_or("Playing index not found")?
                         .parse()
                         .map_err(|_| "Playing index is not a number")?;
-                    Ok(Status::Playing(index))
+                    let hand = indices
+                        .next()
+                        .ok_or("Hand index not found")?
+                        .parse()
+                        .map_err(|_| "Hand index is not a number")?;
+                    Ok(Status::Playing(index, hand))
                 } else {
                     Err("Invalid status".to_owned())
                 }
@@ -36,15 +44,16 @@ impl FromStr for Status {
     }
 }
 
-// ToStringトレイトの実装
-impl ToString for Status {
-    fn to_string(&self) -> String {
+// Displayトレイトの実装
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Status::Betting => "Betting".to_string(),
-            Status::Dealing => "Dealing".to_string(),
-            Status::DealerTurn => "DealerTurn".to_string(),
-            Status::End => "End".to_string(),
-            Status::Playing(i) => format!("Playing {}", i),
+            Status::Betting => write!(f, "Betting"),
+            Status::Dealing => write!(f, "Dealing"),
+            Status::Insurance => write!(f, "Insurance"),
+            Status::DealerTurn => write!(f, "DealerTurn"),
+            Status::End => write!(f, "End"),
+            Status::Playing(i, hand) => write!(f, "Playing {} {}", i, hand),
         }
     }
 }
@@ -1,7 +1,14 @@
+use std::collections::HashMap;
+
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+
 use super::{
     card::Card,
-    deck::Deck,
+    fair,
+    rules::{Surrender, TableRules},
+    shoe::Shoe,
     state::{Effect, State},
+    SKIP_LIMIT, TIMEOUT_LIMIT,
 };
 
 #[derive(Debug, Clone)]
@@ -10,53 +17,293 @@ pub enum Command {
     Participate(String),
     Leave(String),
     Bet(String, u32),
+    Ready(String),
     Hit(String),
     Stand(String),
+    DoubleDown(String),
+    Split(String),
+    Insurance(String),
+    EvenMoney(String),
+    Surrender(String),
+    ClientSeed(String, String),
+    // テーブルの管理者のみが送れる
+    Stop(String),
+    Pause(String),
+    Resume(String),
 }
 
 impl Command {
+    pub fn get_name(&self) -> &str {
+        match self {
+            Command::Ping(name)
+            | Command::Participate(name)
+            | Command::Leave(name)
+            | Command::Bet(name, _)
+            | Command::Ready(name)
+            | Command::Hit(name)
+            | Command::Stand(name)
+            | Command::DoubleDown(name)
+            | Command::Split(name)
+            | Command::Insurance(name)
+            | Command::EvenMoney(name)
+            | Command::Surrender(name)
+            | Command::ClientSeed(name, _)
+            | Command::Stop(name)
+            | Command::Pause(name)
+            | Command::Resume(name) => name,
+        }
+    }
+
     pub fn success_message(&self) -> String {
         match self {
             Command::Ping(name) => format!("pong, {}", name),
             Command::Participate(name) => format!("{name}さんが参加しました。"),
             Command::Leave(name) => format!("{name}さんが退出しました。"),
             Command::Bet(name, amount) => format!("{name}さんが{amount}コイン賭けました。"),
+            Command::Ready(name) => format!("{name}さんの準備ができました。"),
             Command::Hit(name) => format!("{name}さんがヒットしました。"),
             Command::Stand(name) => format!("{name}さんがスタンドしました。"),
+            Command::DoubleDown(name) => format!("{name}さんがダブルダウンしました。"),
+            Command::Split(name) => format!("{name}さんがスプリットしました。"),
+            Command::Insurance(name) => format!("{name}さんがインシュランスをかけました。"),
+            Command::EvenMoney(name) => format!("{name}さんがイーブンマネーを選びました。"),
+            Command::Surrender(name) => format!("{name}さんがサレンダーしました。"),
+            Command::ClientSeed(name, seed) => {
+                format!("{name}さんがクライアントシードを{seed}に設定しました。")
+            }
+            Command::Stop(name) => {
+                format!("{name}さんがテーブルを閉じます。このラウンドが最後です。")
+            }
+            Command::Pause(name) => format!("{name}さんがゲームを一時停止しました。"),
+            Command::Resume(name) => format!("{name}さんがゲームを再開しました。"),
         }
     }
 }
 
 pub struct Table {
     state: State,
-    deck: Deck,
+    shoe: Shoe,
+    rules: TableRules,
+    // サーバーシードを生成する
+    rng: StdRng,
+    server_seed: String,
+    client_seed: String,
+    // シューを戻した後の最初のラウンドではカードを1枚捨てる
+    refilled: bool,
+    // このラウンドで配ったカード
+    dealt: Vec<Card>,
+    // このラウンドでシャッフルし直した回数
+    reshuffles: u32,
+    // プレイヤーごとの連続して時間切れになった回数
+    timeouts: HashMap<String, u32>,
+    // プレイヤーごとの連続してベットせずに見送った回数
+    skips: HashMap<String, u32>,
+    // ラウンドを見送ったプレイヤーも含めた席順
+    seats: Vec<String>,
+    paused: bool,
+    // このラウンドが終わったらテーブルを閉じる
+    stopping: bool,
 }
 
 impl Table {
-    pub fn new() -> Table {
-        let mut deck = Deck::new();
-        deck.shuffle();
+    pub fn new(rules: TableRules) -> Table {
+        Table::with_seed(rules, thread_rng().gen())
+    }
+
+    // 同じシードからは同じ順番でラウンドのシードが作られる
+    pub fn with_seed(rules: TableRules, seed: u64) -> Table {
+        let shoe = Shoe::new(rules.decks, rules.penetration, rules.burn_card);
+        Table::with_shoe(rules, shoe, seed)
+    }
 
+    fn with_shoe(rules: TableRules, shoe: Shoe, seed: u64) -> Table {
         Table {
             state: State::new(),
-            deck,
+            shoe,
+            rules,
+            rng: StdRng::seed_from_u64(seed),
+            server_seed: String::new(),
+            client_seed: String::new(),
+            refilled: false,
+            dealt: Vec::new(),
+            reshuffles: 0,
+            timeouts: HashMap::new(),
+            skips: HashMap::new(),
+            seats: Vec::new(),
+            paused: false,
+            stopping: false,
         }
     }
 
-    pub fn init_players(&mut self, players: Vec<String>) {
-        let effect = Effect::Init(players);
-        self.state.apply_effect(effect);
+    // 前のラウンドのプレイヤーで次のラウンドを始める
+    pub fn init_round(&mut self) -> Vec<Effect> {
+        let mut effects = vec![];
+
+        // カットカードが出ていればラウンドの前にシューを戻す
+        if self.shoe.needs_shuffle() {
+            self.shoe.refill();
+            self.refilled = true;
+            effects.push(Effect::Shuffle);
+        }
+
+        // 前のラウンドを見送ったプレイヤーも席に戻す
+        let seats = match std::mem::take(&mut self.seats) {
+            seats if seats.is_empty() => self.state.get_player_order(),
+            seats => seats,
+        };
+
+        // 時間切れやベットの見送りが続いたプレイヤーは席から外す
+        let mut player_order = vec![];
+        for name in seats {
+            let timed_out = self
+                .timeouts
+                .get(&name)
+                .is_some_and(|count| *count >= TIMEOUT_LIMIT);
+            let skipped = self
+                .skips
+                .get(&name)
+                .is_some_and(|count| *count >= SKIP_LIMIT);
+            if timed_out || skipped {
+                self.timeouts.remove(&name);
+                self.skips.remove(&name);
+                let effect = Effect::RemovePlayer(name);
+                self.state.apply_effect(effect.clone());
+                effects.push(effect);
+            } else {
+                player_order.push(name);
+            }
+        }
+
+        let effect = Effect::Init(player_order);
+        self.state.apply_effect(effect.clone());
+        effects.push(effect);
+
+        // サーバーシードはハッシュだけを先に公開し、ラウンドの後に明かす
+        let server_seed: [u8; 32] = self.rng.gen();
+        self.server_seed = hex::encode(server_seed);
+        effects.push(Effect::Commit(fair::commitment(&self.server_seed)));
+
+        effects
+    }
+
+    // ベットの締め切り後、クライアントシードと合わせてシャッフルする。
+    // 残りのカードとシードを記録しておけばラウンドを再現できる
+    fn shuffle(&mut self, effects: &mut Vec<Effect>) {
+        let seed = fair::round_seed(&self.server_seed, &self.client_seed);
+        let cards = self.shoe.get_cards();
+        self.shoe.shuffle(seed);
+        self.dealt.clear();
+        self.reshuffles = 0;
+        effects.push(Effect::RoundSeed(seed, cards));
+
+        if self.refilled {
+            if let Some(card) = self.shoe.burn() {
+                effects.push(Effect::Burn(card));
+            }
+            self.refilled = false;
+        }
+    }
+
+    // ラウンドを終えてサーバーシードを公開する
+    fn finish(&mut self) -> Vec<Effect> {
+        let mut effects = vec![];
+
+        let effect = Effect::Finish;
+        self.state.apply_effect(effect.clone());
+        effects.push(effect);
+
+        effects.push(Effect::Reveal(
+            self.server_seed.clone(),
+            self.client_seed.clone(),
+        ));
+
+        effects
+    }
+
+    // ラウンドの途中でシューが尽きた場合は、場に出ていないカードをシャッフルし直す。
+    // シードはサーバーシードから導くので、公開後に検証できる
+    fn draw(&mut self, effects: &mut Vec<Effect>) -> Card {
+        let card = match self.shoe.draw() {
+            Some(card) => card,
+            None => {
+                self.reshuffles += 1;
+                let seed =
+                    fair::reshuffle_seed(&self.server_seed, &self.client_seed, self.reshuffles);
+                self.shoe.refill_except(&self.dealt);
+                let cards = self.shoe.get_cards();
+                self.shoe.shuffle(seed);
+                effects.push(Effect::Reshuffle(seed, cards));
+                self.shoe.draw().unwrap()
+            }
+        };
+        self.dealt.push(card);
+        card
+    }
+
+    // 一時停止中は再開と終了以外のコマンドを受け付けない
+    fn check_paused(&self, command: &Command) -> Result<(), String> {
+        if self.paused
+            && !matches!(
+                command,
+                Command::Ping(_) | Command::Stop(_) | Command::Resume(_)
+            )
+        {
+            return Err("Game is paused".to_string());
+        }
+
+        Ok(())
+    }
+
+    // コマンドを適用する前に取り置く掛け金。適用できないコマンドはエラーになる
+    pub fn get_stake(&self, command: &Command) -> Result<Option<u32>, String> {
+        self.check_paused(command)?;
+
+        let stake = match command {
+            Command::Bet(name, amount) => self.bet_stake(name, *amount)?,
+            Command::DoubleDown(name) => self.double_down_stake(name)?,
+            Command::Split(name) => self.split_stake(name)?,
+            Command::Insurance(name) => self.insurance_stake(name)?,
+            _ => return Ok(None),
+        };
+        Ok(Some(stake))
     }
 
     pub fn apply_command(&mut self, command: Command) -> Result<Vec<Effect>, String> {
-        match command {
+        let name = command.get_name().to_string();
+        let acted = matches!(
+            command,
+            Command::Hit(_)
+                | Command::Stand(_)
+                | Command::DoubleDown(_)
+                | Command::Split(_)
+                | Command::Surrender(_)
+        );
+        self.check_paused(&command)?;
+
+        let effects = match command {
             Command::Ping(_) => Ok(vec![]),
             Command::Participate(name) => self.participate(name),
             Command::Leave(name) => self.leave(&name),
             Command::Bet(name, amount) => self.bet(&name, amount),
+            Command::Ready(name) => self.ready(&name),
             Command::Hit(name) => self.hit(&name),
             Command::Stand(name) => self.stand(&name),
+            Command::DoubleDown(name) => self.double_down(&name),
+            Command::Split(name) => self.split(&name),
+            Command::Insurance(name) => self.insurance(&name),
+            Command::EvenMoney(name) => self.even_money(&name),
+            Command::Surrender(name) => self.surrender(&name),
+            Command::ClientSeed(name, seed) => self.client_seed(&name, seed),
+            Command::Stop(_) => self.stop(),
+            Command::Pause(_) => self.pause(),
+            Command::Resume(_) => self.resume(),
+        }?;
+
+        // 自分のハンドをプレイしたら時間切れの回数を戻す
+        if acted {
+            self.timeouts.remove(&name);
         }
+        Ok(effects)
     }
 
     fn participate(&mut self, name: String) -> Result<Vec<Effect>, String> {
@@ -78,29 +325,99 @@ impl Table {
             return Err("Game has already started".to_string());
         }
 
-        if !self.state.has_player(name) {
-            return Err("Player does not exist".to_string());
+        let player = self
+            .state
+            .get_player(name)
+            .ok_or("Player does not exist".to_string())?;
+
+        let mut effects = vec![];
+
+        // ベット済みの掛け金は返す
+        let amount = player.get_amount();
+        if amount > 0 {
+            effects.push(Effect::Refund(name.to_string(), amount));
         }
 
         let effect = Effect::RemovePlayer(name.to_string());
         self.state.apply_effect(effect.clone());
-        Ok(vec![effect])
+        effects.push(effect);
+        self.timeouts.remove(name);
+        self.skips.remove(name);
+
+        Ok(effects)
     }
 
     fn bet(&mut self, name: &str, amount: u32) -> Result<Vec<Effect>, String> {
+        self.bet_stake(name, amount)?;
+
+        let effect = Effect::Bet(name.to_string(), amount);
+        self.state.apply_effect(effect.clone());
+        Ok(vec![effect])
+    }
+
+    fn bet_stake(&self, name: &str, amount: u32) -> Result<u32, String> {
         if !self.state.is_betting() {
             return Err("Game has already started".to_string());
         }
 
-        if !self.state.has_player(name) {
-            return Err("Player does not exist".to_string());
+        if amount == 0 {
+            return Err("Bet must be at least 1".to_string());
         }
 
-        let effect = Effect::Bet(name.to_string(), amount);
+        let player = self
+            .state
+            .get_player(name)
+            .ok_or("Player does not exist".to_string())?;
+
+        let total = player.get_amount() + amount;
+        if total < self.rules.min_bet {
+            return Err(format!("The minimum bet is {}", self.rules.min_bet));
+        }
+        if total > self.rules.max_bet {
+            return Err(format!("The maximum bet is {}", self.rules.max_bet));
+        }
+
+        Ok(amount)
+    }
+
+    fn ready(&mut self, name: &str) -> Result<Vec<Effect>, String> {
+        if !self.state.is_betting() {
+            return Err("Game has already started".to_string());
+        }
+
+        let player = self
+            .state
+            .get_player(name)
+            .ok_or("Player does not exist".to_string())?;
+        if player.get_amount() == 0 {
+            return Err("Place a bet before getting ready".to_string());
+        }
+        if player.is_ready() {
+            return Err("You are already ready".to_string());
+        }
+
+        let effect = Effect::Ready(name.to_string());
         self.state.apply_effect(effect.clone());
         Ok(vec![effect])
     }
 
+    fn client_seed(&mut self, name: &str, seed: String) -> Result<Vec<Effect>, String> {
+        if !self.state.is_betting() {
+            return Err("Game has already started".to_string());
+        }
+
+        if !self.state.has_player(name) {
+            return Err("Player does not exist".to_string());
+        }
+
+        if seed.is_empty() || seed.len() > 64 {
+            return Err("Client seed must be 1 to 64 characters".to_string());
+        }
+
+        self.client_seed = seed.clone();
+        Ok(vec![Effect::ClientSeed(name.to_string(), seed)])
+    }
+
     pub fn start(&mut self) -> Result<Vec<Effect>, String> {
         if !self.state.is_betting() {
             return Err("Game has already started".to_string());
@@ -112,18 +429,40 @@ impl Table {
 
         let mut effects = vec![];
 
+        // ベットしなかったプレイヤーはこのラウンドを見送る
+        self.seats = self.state.get_player_order();
+        for name in self.state.get_player_order() {
+            if self.state.get_player(&name).unwrap().get_amount() > 0 {
+                self.skips.remove(&name);
+                continue;
+            }
+
+            let count = self.skips.entry(name.clone()).or_insert(0);
+            *count += 1;
+            let effect = Effect::SitOut(name, *count);
+            self.state.apply_effect(effect.clone());
+            effects.push(effect);
+        }
+
+        // 誰もベットしなかった場合は配らずに次のラウンドに進む
+        if self.state.get_player_count() == 0 {
+            return Ok(effects);
+        }
+
         let effect = Effect::Start;
         self.state.apply_effect(effect.clone());
         effects.push(effect);
 
+        self.shuffle(&mut effects);
+
         let mut player_cards = std::collections::HashMap::new();
         for name in self.state.get_player_order() {
-            let card1 = self.deck.draw().ok_or("Deck is empty".to_string())?;
-            let card2 = self.deck.draw().ok_or("Deck is empty".to_string())?;
+            let card1 = self.draw(&mut effects);
+            let card2 = self.draw(&mut effects);
             player_cards.insert(name.clone(), (card1, card2));
         }
-        let dealer_card1 = self.deck.draw().ok_or("Deck is empty".to_string())?;
-        let dealer_card2 = self.deck.draw().ok_or("Deck is empty".to_string())?;
+        let dealer_card1 = self.draw(&mut effects);
+        let dealer_card2 = self.draw(&mut effects);
         let dummy = Card::new_hidden();
 
         let effect = Effect::Deal(player_cards.clone(), (dealer_card1, dealer_card2));
@@ -131,25 +470,179 @@ impl Table {
         let dummy_effect = Effect::Deal(player_cards, (dealer_card1, dummy));
         effects.push(dummy_effect);
 
-        if self.state.get_dealer_score() == 21 {
-            let effect = Effect::DealerBlackjack;
+        // アップカードがエースの場合はピークの前にインシュランスを受け付ける
+        if dealer_card1.is_ace() {
+            let effect = Effect::OfferInsurance;
             self.state.apply_effect(effect.clone());
             effects.push(effect);
-            let effect = Effect::Finish;
+        } else if self.rules.surrender == Surrender::Early && dealer_card1.get_score(true) == 10 {
+            // アーリーサレンダーはピークの前に受け付ける
+            let effect = Effect::OfferSurrender;
             self.state.apply_effect(effect.clone());
             effects.push(effect);
-            let effect = Effect::Init(self.state.get_player_order());
+        } else if self.state.is_dealer_blackjack() {
+            effects.append(&mut self.dealer_blackjack());
+        } else {
+            effects.append(&mut self.next_player()?);
+        }
+
+        Ok(effects)
+    }
+
+    fn insurance(&mut self, name: &str) -> Result<Vec<Effect>, String> {
+        let amount = self.insurance_stake(name)?;
+
+        let effect = Effect::Insurance(name.to_string(), amount);
+        self.state.apply_effect(effect.clone());
+        Ok(vec![effect])
+    }
+
+    // インシュランスは掛け金の半額。奇数の掛け金の端数は切り捨てる
+    fn insurance_stake(&self, name: &str) -> Result<u32, String> {
+        if !self.state.is_insurance() {
+            return Err("Insurance is not available now".to_string());
+        }
+        if !self.state.get_dealer_hands(false)[0].is_ace() {
+            return Err("Insurance is only offered when the dealer shows an ace".to_string());
+        }
+
+        let player = self
+            .state
+            .get_player(name)
+            .ok_or("Player does not exist".to_string())?;
+        if player.get_insurance() > 0
+            || player.has_even_money()
+            || player.get_hand(0).is_surrendered()
+        {
+            return Err("You have already made a decision".to_string());
+        }
+        if player.get_hand(0).value().is_blackjack {
+            return Err("You have blackjack. Take even money instead".to_string());
+        }
+
+        let amount = player.get_amount() / 2;
+        if amount == 0 {
+            return Err("Your bet is too small to insure".to_string());
+        }
+
+        Ok(amount)
+    }
+
+    fn even_money(&mut self, name: &str) -> Result<Vec<Effect>, String> {
+        if !self.state.is_insurance() {
+            return Err("Even money is not available now".to_string());
+        }
+        if !self.state.get_dealer_hands(false)[0].is_ace() {
+            return Err("Even money is only offered when the dealer shows an ace".to_string());
+        }
+
+        let player = self
+            .state
+            .get_player(name)
+            .ok_or("Player does not exist".to_string())?;
+        if player.get_insurance() > 0
+            || player.has_even_money()
+            || player.get_hand(0).is_surrendered()
+        {
+            return Err("You have already made a decision".to_string());
+        }
+        if !player.get_hand(0).value().is_blackjack {
+            return Err("Even money is only for blackjack".to_string());
+        }
+
+        let effect = Effect::EvenMoney(name.to_string());
+        self.state.apply_effect(effect.clone());
+        Ok(vec![effect])
+    }
+
+    fn surrender(&mut self, name: &str) -> Result<Vec<Effect>, String> {
+        if self.rules.surrender == Surrender::Disabled {
+            return Err("Surrender is not allowed at this table".to_string());
+        }
+
+        // ピーク前のサレンダーはアーリーサレンダーのルールでのみ可能
+        if self.state.is_insurance() {
+            if self.rules.surrender != Surrender::Early {
+                return Err("Early surrender is not allowed at this table".to_string());
+            }
+
+            let player = self
+                .state
+                .get_player(name)
+                .ok_or("Player does not exist".to_string())?;
+            if player.get_insurance() > 0
+                || player.has_even_money()
+                || player.get_hand(0).is_surrendered()
+            {
+                return Err("You have already made a decision".to_string());
+            }
+
+            let effect = Effect::Surrender(name.to_string());
             self.state.apply_effect(effect.clone());
-            effects.push(effect);
+            return Ok(vec![effect]);
+        }
+
+        match self.state.get_current_player() {
+            Some(player) => {
+                if player.name != name {
+                    return Err("It's not your turn".to_string());
+                }
+                if player.get_hand_count() != 1 || player.get_hand(0).get_card_count() != 2 {
+                    return Err("Surrender is only allowed as your first decision".to_string());
+                }
+            }
+            None => return Err("Game has not started yet".to_string()),
+        }
+
+        let mut effects = vec![];
+
+        let effect = Effect::Surrender(name.to_string());
+        self.state.apply_effect(effect.clone());
+        effects.push(effect);
+
+        effects.append(&mut self.next_player()?);
+
+        Ok(effects)
+    }
+
+    // インシュランスの受付を締め切り、ディーラーのホールカードを確認する
+    pub fn close_insurance(&mut self) -> Result<Vec<Effect>, String> {
+        if !self.state.is_insurance() {
+            return Err("Insurance is not available now".to_string());
+        }
+
+        let mut effects = vec![];
+
+        if self.state.is_dealer_blackjack() {
+            effects.append(&mut self.dealer_blackjack());
         } else {
-            let effect = Effect::NextPlayer;
+            let effect = Effect::CloseInsurance;
             self.state.apply_effect(effect.clone());
             effects.push(effect);
+            effects.append(&mut self.next_player()?);
         }
 
         Ok(effects)
     }
 
+    // ディーラーのブラックジャックを公開してゲームを終える
+    fn dealer_blackjack(&mut self) -> Vec<Effect> {
+        let mut effects = vec![];
+
+        let effect = Effect::DealerBlackjack;
+        self.state.apply_effect(effect.clone());
+        effects.push(effect);
+
+        let hidden_card = *self.state.get_dealer_hands(false).get(1).unwrap();
+        let effect = Effect::OpenDealerCard(hidden_card);
+        self.state.apply_effect(effect.clone());
+        effects.push(effect);
+
+        effects.append(&mut self.finish());
+
+        effects
+    }
+
     fn hit(&mut self, name: &str) -> Result<Vec<Effect>, String> {
         match self.state.get_current_player() {
             Some(player) => {
@@ -162,12 +655,12 @@ impl Table {
 
         let mut effects = vec![];
 
-        let card = self.deck.draw().ok_or("Deck is empty".to_string())?;
+        let card = self.draw(&mut effects);
         let effect = Effect::AddCard(name.to_string(), card);
         self.state.apply_effect(effect.clone());
         effects.push(effect);
 
-        if self.state.get_current_player().unwrap().get_score() > 21 {
+        if self.state.get_current_hand().unwrap().value().is_bust {
             effects.push(Effect::Burst(name.to_string()));
             effects.append(&mut self.stand(name)?);
         }
@@ -185,9 +678,207 @@ impl Table {
             None => return Err("Game has not started yet".to_string()),
         }
 
+        self.next_player()
+    }
+
+    // ベット中であれば掛け金を返してすぐに、それ以外はラウンドが終わってから閉じる
+    fn stop(&mut self) -> Result<Vec<Effect>, String> {
+        if self.stopping {
+            return Err("Table is already closing".to_string());
+        }
+
+        let mut effects = vec![];
+        if self.paused {
+            effects.append(&mut self.resume()?);
+        }
+
+        if self.state.is_betting() {
+            for name in self.state.get_player_order() {
+                let amount = self.state.get_player(&name).unwrap().get_amount();
+                if amount > 0 {
+                    effects.push(Effect::Refund(name, amount));
+                }
+            }
+        }
+
+        self.stopping = true;
+        effects.push(Effect::Stop);
+        Ok(effects)
+    }
+
+    fn pause(&mut self) -> Result<Vec<Effect>, String> {
+        if self.paused {
+            return Err("Game is already paused".to_string());
+        }
+
+        self.paused = true;
+        Ok(vec![Effect::Pause])
+    }
+
+    fn resume(&mut self) -> Result<Vec<Effect>, String> {
+        if !self.paused {
+            return Err("Game is not paused".to_string());
+        }
+
+        self.paused = false;
+        Ok(vec![Effect::Resume])
+    }
+
+    // ターンの制限時間が近いことを知らせる
+    pub fn warn_turn(&self, seconds: u64) -> Vec<Effect> {
+        match self.state.get_current_player() {
+            Some(player) => vec![Effect::TurnWarning(player.name.clone(), seconds)],
+            None => vec![],
+        }
+    }
+
+    // ターンの制限時間を過ぎたプレイヤーをスタンドさせる
+    pub fn time_out(&mut self) -> Result<Vec<Effect>, String> {
+        let name = self
+            .state
+            .get_current_player()
+            .ok_or("Game has not started yet".to_string())?
+            .name
+            .clone();
+
+        let count = self.timeouts.entry(name.clone()).or_insert(0);
+        *count += 1;
+        let mut effects = vec![Effect::TimedOut(name.clone(), *count)];
+        effects.append(&mut self.stand(&name)?);
+
+        Ok(effects)
+    }
+
+    // 次のハンドに進む。スプリットされたハンドには2枚目のカードを配る
+    fn next_player(&mut self) -> Result<Vec<Effect>, String> {
+        let mut effects = vec![];
+
         let effect = Effect::NextPlayer;
         self.state.apply_effect(effect.clone());
-        Ok(vec![effect])
+        effects.push(effect);
+
+        // アーリーサレンダーしたハンドとナチュラルはプレイしない
+        if let (Some(player), Some(hand)) = (
+            self.state.get_current_player(),
+            self.state.get_current_hand(),
+        ) {
+            if hand.is_surrendered() || player.is_natural() {
+                effects.append(&mut self.next_player()?);
+                return Ok(effects);
+            }
+        }
+
+        let (name, split_aces) = match (
+            self.state.get_current_player(),
+            self.state.get_current_hand(),
+        ) {
+            (Some(player), Some(hand)) if hand.get_card_count() == 1 => {
+                (player.name.clone(), hand.get_cards()[0].is_ace())
+            }
+            _ => return Ok(effects),
+        };
+
+        let card = self.draw(&mut effects);
+        let effect = Effect::AddCard(name, card);
+        self.state.apply_effect(effect.clone());
+        effects.push(effect);
+
+        if split_aces && self.rules.split_aces_one_card {
+            effects.append(&mut self.next_player()?);
+        }
+
+        Ok(effects)
+    }
+
+    fn double_down(&mut self, name: &str) -> Result<Vec<Effect>, String> {
+        let amount = self.double_down_stake(name)?;
+
+        let mut effects = vec![];
+
+        let effect = Effect::DoubleDown(name.to_string(), amount);
+        self.state.apply_effect(effect.clone());
+        effects.push(effect);
+
+        let card = self.draw(&mut effects);
+        let effect = Effect::AddCard(name.to_string(), card);
+        self.state.apply_effect(effect.clone());
+        effects.push(effect);
+
+        if self.state.get_current_hand().unwrap().value().is_bust {
+            effects.push(Effect::Burst(name.to_string()));
+        }
+        effects.append(&mut self.stand(name)?);
+
+        Ok(effects)
+    }
+
+    // ダブルダウンではハンドの掛け金と同額を追加する
+    fn double_down_stake(&self, name: &str) -> Result<u32, String> {
+        match self.state.get_current_player() {
+            Some(player) => {
+                if player.name != name {
+                    return Err("It's not your turn".to_string());
+                }
+            }
+            None => return Err("Game has not started yet".to_string()),
+        }
+
+        let hand = self.state.get_current_hand().unwrap();
+        if hand.get_card_count() != 2 {
+            return Err("You can only double down on your first two cards".to_string());
+        }
+        if hand.is_split() && !self.rules.double_after_split {
+            return Err("Double down after split is not allowed".to_string());
+        }
+
+        Ok(hand.get_amount())
+    }
+
+    fn split(&mut self, name: &str) -> Result<Vec<Effect>, String> {
+        let amount = self.split_stake(name)?;
+
+        let mut effects = vec![];
+
+        let split_aces = self.state.get_current_hand().unwrap().is_ace_pair();
+        let effect = Effect::Split(name.to_string(), amount);
+        self.state.apply_effect(effect.clone());
+        effects.push(effect);
+
+        let card = self.draw(&mut effects);
+        let effect = Effect::AddCard(name.to_string(), card);
+        self.state.apply_effect(effect.clone());
+        effects.push(effect);
+
+        if split_aces && self.rules.split_aces_one_card {
+            effects.append(&mut self.next_player()?);
+        }
+
+        Ok(effects)
+    }
+
+    // スプリットした新しいハンドには同額を賭ける
+    fn split_stake(&self, name: &str) -> Result<u32, String> {
+        match self.state.get_current_player() {
+            Some(player) => {
+                if player.name != name {
+                    return Err("It's not your turn".to_string());
+                }
+                if player.get_hand_count() >= self.rules.max_split_hands {
+                    return Err("You cannot split any more hands".to_string());
+                }
+            }
+            None => return Err("Game has not started yet".to_string()),
+        }
+
+        let hand = self.state.get_current_hand().unwrap();
+        if !hand.is_pair() {
+            return Err("You can only split a pair".to_string());
+        }
+        if hand.is_split() && !self.rules.resplit {
+            return Err("Re-splitting is not allowed".to_string());
+        }
+
+        Ok(hand.get_amount())
     }
 
     pub fn dealer_action(&mut self) -> Result<Vec<Effect>, String> {
@@ -202,32 +893,63 @@ impl Table {
         self.state.apply_effect(effect.clone());
         effects.push(effect);
 
-        while self.state.get_dealer_score() < 17 {
-            let card = self.deck.draw().ok_or("Deck is empty".to_string())?;
+        while self.dealer_should_hit() {
+            let card = self.draw(&mut effects);
             let effect = Effect::AddDealerCard(card);
             self.state.apply_effect(effect.clone());
             effects.push(effect);
         }
 
-        if self.state.get_dealer_score() > 21 {
+        if self.state.get_dealer_value().is_bust {
             let effect = Effect::DealerBurst;
             self.state.apply_effect(effect.clone());
             effects.push(effect);
         }
 
-        let effect = Effect::Finish;
-        self.state.apply_effect(effect.clone());
-        effects.push(effect);
+        effects.append(&mut self.finish());
 
         Ok(effects)
     }
 
+    fn dealer_should_hit(&self) -> bool {
+        let value = self.state.get_dealer_value();
+        value.total < 17 || (value.total == 17 && value.soft && self.rules.dealer_hits_soft_17)
+    }
+
+    pub fn is_insurance(&self) -> bool {
+        self.state.is_insurance()
+    }
+
     pub fn is_dealer_turn(&self) -> bool {
         self.state.is_dealer_turn()
     }
 
-    pub fn is_started(&self) -> bool {
-        self.state.is_started()
+    pub fn is_betting(&self) -> bool {
+        self.state.is_betting()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn is_stopping(&self) -> bool {
+        self.stopping
+    }
+
+    // ベット中に全員が準備完了していればすぐに始められる
+    pub fn is_all_ready(&self) -> bool {
+        self.state.is_betting() && self.state.is_all_ready()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.state.get_current_player().is_some()
+    }
+
+    // 今ターンのプレイヤーとハンドの番号
+    pub fn get_turn(&self) -> Option<(String, usize)> {
+        let player = self.state.get_current_player()?;
+        let hand = self.state.get_current_hand_index()?;
+        Some((player.name.clone(), hand))
     }
 
     pub fn is_finished(&self) -> bool {
@@ -238,11 +960,476 @@ impl Table {
         self.state.get_player_count()
     }
 
-    pub fn get_players(&self) -> Vec<String> {
-        self.state.get_player_order()
+    // 今のラウンドのコミットメント。ラウンドを識別するのに使う
+    pub fn get_commitment(&self) -> String {
+        fair::commitment(&self.server_seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn stacked_table(cards: &[&str]) -> Table {
+        stacked_table_with_rules(TableRules::default(), cards)
+    }
+
+    fn stacked_table_with_rules(rules: TableRules, cards: &[&str]) -> Table {
+        let cards = cards.iter().map(|s| Card::from_str(s).unwrap()).collect();
+        let mut table = Table::with_shoe(rules, Shoe::stacked(cards), 0);
+        table.init_round();
+        table
+    }
+
+    fn seat(table: &mut Table, name: &str, amount: u32) {
+        table
+            .apply_command(Command::Participate(name.to_string()))
+            .unwrap();
+        table
+            .apply_command(Command::Bet(name.to_string(), amount))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_same_seed_deals_same_cards() {
+        let mut tables = [
+            Table::with_seed(TableRules::default(), 42),
+            Table::with_seed(TableRules::default(), 42),
+        ];
+        for table in tables.iter_mut() {
+            table.init_round();
+            seat(table, "a", 10);
+            table.start().unwrap();
+        }
+
+        assert_eq!(tables[0].state.to_string(), tables[1].state.to_string());
+    }
+
+    #[test]
+    fn test_burn_card_is_first_in_verified_order() {
+        let mut table = Table::with_seed(TableRules::default(), 42);
+        table.init_round();
+        seat(&mut table, "a", 10);
+        let effects = table.start().unwrap();
+
+        let shoe = effects
+            .iter()
+            .find_map(|effect| match effect {
+                Effect::RoundSeed(_, cards) => Some(cards.clone()),
+                _ => None,
+            })
+            .unwrap();
+        let burned = effects
+            .iter()
+            .find_map(|effect| match effect {
+                Effect::Burn(card) => Some(*card),
+                _ => None,
+            })
+            .unwrap();
+        let shoe = shoe
+            .iter()
+            .map(|card| card.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let order = fair::verify(
+            &table.get_commitment(),
+            &table.server_seed,
+            &table.client_seed,
+            &shoe,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(order[0], burned.to_string());
+        assert_eq!(order.len(), 52);
+    }
+
+    #[test]
+    fn test_natural_pushes_dealer_blackjack() {
+        let mut table = stacked_table(&["SA", "HK", "D10", "CA"]);
+        seat(&mut table, "a", 10);
+        table.start().unwrap();
+
+        assert!(table.is_finished());
+        assert_eq!(table.state.get_result(&table.rules)["a"], (10, 0));
+    }
+
+    #[test]
+    fn test_natural_payout() {
+        for (payout, result) in [("3:2", (25, 15)), ("6:5", (22, 12))] {
+            let rules = TableRules {
+                blackjack_payout: payout.parse().unwrap(),
+                ..TableRules::default()
+            };
+            let mut table = stacked_table_with_rules(rules, &["SA", "HK", "S10", "C7"]);
+            seat(&mut table, "a", 10);
+            table.start().unwrap();
+
+            // ナチュラルはプレイせずにディーラーのターンになる
+            assert!(table.is_dealer_turn());
+            table.dealer_action().unwrap();
+            assert_eq!(table.state.get_result(&table.rules)["a"], result);
+        }
+    }
+
+    #[test]
+    fn test_double_down() {
+        let mut table = stacked_table(&["H5", "D6", "S10", "C7", "H10"]);
+        seat(&mut table, "a", 10);
+        table.start().unwrap();
+        table
+            .apply_command(Command::DoubleDown("a".to_string()))
+            .unwrap();
+
+        assert!(table.is_dealer_turn());
+        table.dealer_action().unwrap();
+        assert_eq!(table.state.get_result(&table.rules)["a"], (40, 20));
+    }
+
+    #[test]
+    fn test_double_down_only_on_first_two_cards() {
+        let mut table = stacked_table(&["H5", "D6", "S10", "C7", "H2"]);
+        seat(&mut table, "a", 10);
+        table.start().unwrap();
+
+        // 自分のターン以外はダブルダウンできない
+        assert!(table
+            .apply_command(Command::DoubleDown("b".to_string()))
+            .is_err());
+
+        table.apply_command(Command::Hit("a".to_string())).unwrap();
+        assert!(!table.state.can_double_down(&table.rules));
+        assert!(table
+            .apply_command(Command::DoubleDown("a".to_string()))
+            .is_err());
+        assert_eq!(table.state.get_player("a").unwrap().get_amount(), 10);
+    }
+
+    #[test]
+    fn test_split() {
+        let mut table = stacked_table(&["S8", "H8", "S10", "C7", "D3", "C10"]);
+        seat(&mut table, "a", 10);
+        table.start().unwrap();
+        assert!(table.state.can_split(&table.rules));
+        table
+            .apply_command(Command::Split("a".to_string()))
+            .unwrap();
+        // 8と3の2枚なのでダブルダウンはできるがスプリットはできない
+        assert!(table.state.can_double_down(&table.rules));
+        assert!(!table.state.can_split(&table.rules));
+        table
+            .apply_command(Command::Stand("a".to_string()))
+            .unwrap();
+        table
+            .apply_command(Command::Stand("a".to_string()))
+            .unwrap();
+
+        assert!(table.is_dealer_turn());
+        table.dealer_action().unwrap();
+        // 11は負け、18は勝ち
+        assert_eq!(table.state.get_result(&table.rules)["a"], (20, 0));
+    }
+
+    #[test]
+    fn test_split_aces() {
+        let mut table = stacked_table(&["SA", "HA", "S10", "C7", "D9", "C10"]);
+        seat(&mut table, "a", 10);
+        table.start().unwrap();
+        table
+            .apply_command(Command::Split("a".to_string()))
+            .unwrap();
+
+        // エースのスプリットは1枚ずつ配って終わる
+        assert!(table.is_dealer_turn());
+        table.dealer_action().unwrap();
+        // スプリット後の21はナチュラルではないので1:1
+        assert_eq!(table.state.get_result(&table.rules)["a"], (40, 20));
+    }
+
+    #[test]
+    fn test_resplit_disabled() {
+        let rules = TableRules {
+            resplit: false,
+            ..TableRules::default()
+        };
+        let mut table = stacked_table_with_rules(rules, &["S8", "H8", "S10", "C7", "D8"]);
+        seat(&mut table, "a", 10);
+        table.start().unwrap();
+        table
+            .apply_command(Command::Split("a".to_string()))
+            .unwrap();
+
+        assert!(!table.state.can_split(&table.rules));
+        assert!(table
+            .apply_command(Command::Split("a".to_string()))
+            .is_err());
+    }
+
+    #[test]
+    fn test_insurance_against_dealer_blackjack() {
+        let mut table = stacked_table(&["H10", "D9", "SA", "CK"]);
+        seat(&mut table, "a", 10);
+        table.start().unwrap();
+        assert!(table.is_insurance());
+
+        table
+            .apply_command(Command::Insurance("a".to_string()))
+            .unwrap();
+        table.close_insurance().unwrap();
+
+        // ハンドは負けるが、インシュランスが2:1で支払われる
+        assert!(table.is_finished());
+        assert_eq!(table.state.get_result(&table.rules)["a"], (15, 0));
+    }
+
+    #[test]
+    fn test_insurance_without_dealer_blackjack() {
+        let mut table = stacked_table(&["H10", "D9", "SA", "C7"]);
+        seat(&mut table, "a", 10);
+        table.start().unwrap();
+        table
+            .apply_command(Command::Insurance("a".to_string()))
+            .unwrap();
+        table.close_insurance().unwrap();
+        assert!(table.is_playing());
+
+        table
+            .apply_command(Command::Stand("a".to_string()))
+            .unwrap();
+        table.dealer_action().unwrap();
+        // 19はソフト18に勝つが、インシュランスは没収される
+        assert_eq!(table.state.get_result(&table.rules)["a"], (20, 5));
+    }
+
+    #[test]
+    fn test_even_money() {
+        let mut table = stacked_table(&["SA", "HK", "DA", "C7"]);
+        seat(&mut table, "a", 10);
+        table.start().unwrap();
+
+        // ナチュラルにはインシュランスではなくイーブンマネーを勧める
+        assert!(table
+            .apply_command(Command::Insurance("a".to_string()))
+            .is_err());
+        table
+            .apply_command(Command::EvenMoney("a".to_string()))
+            .unwrap();
+        table.close_insurance().unwrap();
+
+        assert!(table.is_dealer_turn());
+        table.dealer_action().unwrap();
+        assert_eq!(table.state.get_result(&table.rules)["a"], (20, 10));
+    }
+
+    #[test]
+    fn test_late_surrender() {
+        let mut table = stacked_table(&["H10", "D6", "S9", "D7", "S10", "C7", "H2"]);
+        seat(&mut table, "a", 10);
+        seat(&mut table, "b", 10);
+        table.start().unwrap();
+
+        table
+            .apply_command(Command::Surrender("a".to_string()))
+            .unwrap();
+        // 最初の判断でなければサレンダーできない
+        table.apply_command(Command::Hit("b".to_string())).unwrap();
+        assert!(table
+            .apply_command(Command::Surrender("b".to_string()))
+            .is_err());
+        table
+            .apply_command(Command::Stand("b".to_string()))
+            .unwrap();
+
+        table.dealer_action().unwrap();
+        assert_eq!(table.state.get_result(&table.rules)["a"], (5, -5));
+    }
+
+    #[test]
+    fn test_odd_bet_rounds_half_down() {
+        let mut table = stacked_table(&["H10", "D6", "SA", "C7"]);
+        seat(&mut table, "a", 15);
+        table.start().unwrap();
+
+        // インシュランスは15の半額の7
+        assert_eq!(
+            table.get_stake(&Command::Insurance("a".to_string())),
+            Ok(Some(7))
+        );
+        table.close_insurance().unwrap();
+
+        // サレンダーの返金も7で、端数の1は戻らない
+        table
+            .apply_command(Command::Surrender("a".to_string()))
+            .unwrap();
+        table.dealer_action().unwrap();
+        assert_eq!(table.state.get_result(&table.rules)["a"], (7, -8));
+    }
+
+    #[test]
+    fn test_early_surrender() {
+        let rules = TableRules {
+            surrender: Surrender::Early,
+            ..TableRules::default()
+        };
+        let mut table = stacked_table_with_rules(rules, &["H10", "D6", "SK", "CA"]);
+        seat(&mut table, "a", 10);
+        table.start().unwrap();
+        assert!(table.is_insurance());
+
+        table
+            .apply_command(Command::Surrender("a".to_string()))
+            .unwrap();
+        table.close_insurance().unwrap();
+
+        // ディーラーがブラックジャックでも半額が戻る
+        assert!(table.is_finished());
+        assert_eq!(table.state.get_result(&table.rules)["a"], (5, -5));
+    }
+
+    #[test]
+    fn test_early_surrender_disabled() {
+        let mut table = stacked_table(&["H10", "D6", "SA", "C7"]);
+        seat(&mut table, "a", 10);
+        table.start().unwrap();
+
+        // レイトサレンダーのテーブルではピークの前にサレンダーできない
+        assert!(table.is_insurance());
+        assert!(table
+            .apply_command(Command::Surrender("a".to_string()))
+            .is_err());
     }
 
-    pub fn get_player_order(&self) -> Vec<String> {
-        self.state.get_player_order()
+    #[test]
+    fn test_refill_during_round() {
+        let mut table = stacked_table(&["H10", "D6", "S10", "C7"]);
+        seat(&mut table, "a", 10);
+        table.start().unwrap();
+
+        let effects = table.apply_command(Command::Hit("a".to_string())).unwrap();
+        assert!(effects
+            .iter()
+            .any(|effect| matches!(effect, Effect::Reshuffle(_, cards) if cards.len() == 48)));
+
+        // 場に出ているカードはシューに戻さない
+        let mut cards = table.state.get_player("a").unwrap().get_hand(0).get_cards();
+        cards.extend(table.state.get_dealer_hands(false));
+        cards.extend(table.shoe.get_cards());
+        assert_eq!(cards.len(), 52);
+        cards.sort();
+        cards.dedup();
+        assert_eq!(cards.len(), 52);
+    }
+
+    #[test]
+    fn test_time_out() {
+        let mut table = stacked_table(&["H5", "D6", "S10", "C7", "H5", "D6", "S10", "C7"]);
+        table
+            .apply_command(Command::Participate("a".to_string()))
+            .unwrap();
+        for _ in 0..TIMEOUT_LIMIT {
+            table
+                .apply_command(Command::Bet("a".to_string(), 10))
+                .unwrap();
+            table.start().unwrap();
+
+            let effects = table.time_out().unwrap();
+            assert!(matches!(&effects[0], Effect::TimedOut(name, _) if name == "a"));
+            assert!(table.is_dealer_turn());
+            table.dealer_action().unwrap();
+
+            table.init_round();
+        }
+
+        // 時間切れが続いたプレイヤーは次のラウンドで席から外れる
+        assert_eq!(table.get_player_count(), 0);
+    }
+
+    #[test]
+    fn test_ready() {
+        let mut table = Table::with_seed(TableRules::default(), 0);
+        table.init_round();
+        table
+            .apply_command(Command::Participate("c".to_string()))
+            .unwrap();
+        // 誰もベットしていなければ始めない
+        assert!(!table.is_all_ready());
+
+        seat(&mut table, "a", 10);
+        seat(&mut table, "b", 10);
+        table
+            .apply_command(Command::Ready("a".to_string()))
+            .unwrap();
+        assert!(!table.is_all_ready());
+
+        // ベットしていないプレイヤーは準備完了できないが、見送るので待たない
+        assert!(table
+            .apply_command(Command::Ready("c".to_string()))
+            .is_err());
+        table
+            .apply_command(Command::Ready("b".to_string()))
+            .unwrap();
+        assert!(table.is_all_ready());
+    }
+
+    #[test]
+    fn test_sit_out() {
+        let mut table = Table::with_seed(TableRules::default(), 0);
+        table.init_round();
+        seat(&mut table, "a", 10);
+        table
+            .apply_command(Command::Participate("b".to_string()))
+            .unwrap();
+
+        let effects = table.start().unwrap();
+        assert!(effects.contains(&Effect::SitOut("b".to_string(), 1)));
+        assert!(!table.state.has_player("b"));
+
+        // 見送ったプレイヤーも次のラウンドでは席に戻る
+        while !table.is_finished() {
+            if table.is_playing() {
+                table.time_out().unwrap();
+            } else if table.is_insurance() {
+                table.close_insurance().unwrap();
+            } else if table.is_dealer_turn() {
+                table.dealer_action().unwrap();
+            }
+        }
+        table.init_round();
+        assert_eq!(table.state.get_player_order(), vec!["a", "b"]);
+
+        // 見送りが続いたプレイヤーは席から外れる
+        for _ in 1..SKIP_LIMIT {
+            table.start().unwrap();
+            assert!(table.is_betting());
+            table.init_round();
+        }
+        assert_eq!(table.state.get_player_order(), vec!["a"]);
+    }
+
+    #[test]
+    fn test_pause_and_stop() {
+        let mut table = Table::with_seed(TableRules::default(), 0);
+        table.init_round();
+        seat(&mut table, "a", 10);
+
+        table
+            .apply_command(Command::Pause("a".to_string()))
+            .unwrap();
+        assert!(table
+            .apply_command(Command::Bet("a".to_string(), 10))
+            .is_err());
+
+        // ベット中に閉じる場合は掛け金を返す
+        let effects = table.apply_command(Command::Stop("a".to_string())).unwrap();
+        assert_eq!(
+            effects,
+            vec![
+                Effect::Resume,
+                Effect::Refund("a".to_string(), 10),
+                Effect::Stop
+            ]
+        );
+        assert!(!table.is_paused());
+        assert!(table.is_stopping());
     }
 }
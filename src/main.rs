@@ -1,29 +1,41 @@
 use std::env;
+use std::future::Future;
 use std::sync::Mutex;
 use std::{collections::HashMap, sync::Arc};
 
+use async_trait::async_trait;
 use database::{
-    bet, create_bonus, create_discord_user, create_table, delete_table, get_balance, get_bonus,
-    get_table_id, get_username_by_discord, UserId,
+    add_reshuffle, append_event, burn_card, create_bonus, create_discord_user, create_round,
+    create_table, delete_table, find_round, get_balance, get_bonus, get_card_style, get_effects,
+    get_history, get_next_seq, get_reserved, get_round, get_round_record, get_table_creator,
+    get_table_id, get_tables, get_unsettled_rounds, get_username_by_discord, reconcile_balances,
+    release, reserve, reveal_round, set_card_style, settle_round, start_round, void_round,
+    ReserveError, TransactionKind, UserId,
 };
+use discord::DiscordRenderer;
 use dotenvy::dotenv;
+use game::card::{Card, CardStyle};
+use game::history::{finished_rounds, RoundRecord};
+use game::rules::TableRules;
 use game::state::{self, Effect};
 use game::table::Command;
-use game::{Request, Response, BETTING_TIME};
-use serenity::all::ChannelId;
-use serenity::async_trait;
+use game::{Escrow, Request, Response};
+use render::{RoundInfo, TableObserver, TextRenderer};
+use serenity::all::{ChannelId, CommandDataOption, CommandDataOptionValue};
 use serenity::builder::{
-    CreateCommand, CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage,
+    CreateAttachment, CreateCommand, CreateCommandOption, CreateInteractionResponse,
+    CreateInteractionResponseMessage,
 };
 use serenity::client::{Context, EventHandler};
 use serenity::model::prelude::{CommandOptionType, GatewayIntents, Interaction, Ready};
 use sqlx::{Pool, Postgres};
 use tokio::sync::{broadcast, mpsc};
 
-use crate::database::save_result;
-
 mod database;
+mod discord;
 mod game;
+mod render;
+mod sprite;
 
 pub struct Handler {
     game_txs: Arc<Mutex<HashMap<i32, tokio::sync::mpsc::Sender<Request>>>>,
@@ -37,11 +49,203 @@ pub async fn exec_game_command(
     command: Command,
 ) -> String {
     let (res_tx, res_rx) = tokio::sync::oneshot::channel::<Response>();
+    send_request(game_tx, Request::new(res_tx, command), res_rx).await
+}
+
+// インタラクションの番号を添えてコマンドを送る。掛け金は同じインタラクションで二重に取り置かれない
+pub async fn exec_game_command_with_id(
+    game_tx: tokio::sync::mpsc::Sender<Request>,
+    command: Command,
+    interaction_id: u64,
+) -> String {
+    let (res_tx, res_rx) = tokio::sync::oneshot::channel::<Response>();
+    send_request(
+        game_tx,
+        Request::with_id(res_tx, command, interaction_id),
+        res_rx,
+    )
+    .await
+}
+
+async fn send_request(
+    game_tx: tokio::sync::mpsc::Sender<Request>,
+    request: Request,
+    res_rx: tokio::sync::oneshot::Receiver<Response>,
+) -> String {
+    // 記録に失敗してテーブルを止めた場合は、受け付け中のコマンドの返事が来ない
+    let stopped = "ゲームが止まっています。ボットの再起動後にやり直してください。".to_string();
+    if game_tx.send(request).await.is_err() {
+        return stopped;
+    }
+    match res_rx.await {
+        Ok(response) => response.content,
+        Err(_) => stopped,
+    }
+}
+
+// /replayで表示する最近の出来事の数
+const REPLAY_LINES: usize = 10;
+
+const DATABASE_RETRIES: u64 = 3;
+
+// データベースの操作に失敗した場合は少し待ってからやり直す。whatはログに出す操作の説明
+async fn retry<T, F, Fut>(what: &str, mut operation: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let mut attempt = 0;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(why) if attempt < DATABASE_RETRIES => {
+                attempt += 1;
+                println!("Retrying to {what} ({attempt}): {why}");
+                tokio::time::sleep(std::time::Duration::from_secs(attempt)).await;
+            }
+            Err(why) => return Err(why),
+        }
+    }
+}
+
+async fn append_event_with_retry(
+    conn: &Pool<Postgres>,
+    table_id: i32,
+    seq: i32,
+    effect: &Effect,
+) -> Result<(), sqlx::Error> {
+    retry(&format!("append effect {seq} of table {table_id}"), || {
+        append_event(conn, table_id, seq, effect)
+    })
+    .await
+}
+
+async fn settle_round_with_retry(
+    conn: &Pool<Postgres>,
+    table_id: i32,
+    round_id: i32,
+    record: &RoundRecord,
+) -> Result<(), sqlx::Error> {
+    retry(&format!("settle round {round_id}"), || {
+        settle_round(conn, table_id, round_id, record)
+    })
+    .await
+}
+
+// 掛け金をコマンドを受け付ける前にデータベースで取り置く
+struct DatabaseEscrow {
+    conn: Pool<Postgres>,
+    table_id: i32,
+}
+
+impl DatabaseEscrow {
+    // ラウンドはCommitを記録するときにも作るので、コミットメントで同じ行を探す
+    async fn get_round_id(&self, commitment: &str) -> Result<i32, sqlx::Error> {
+        create_round(&self.conn, self.table_id, commitment.to_string()).await
+    }
+}
+
+#[async_trait]
+impl Escrow for DatabaseEscrow {
+    async fn reserve(
+        &mut self,
+        commitment: &str,
+        name: &str,
+        amount: u32,
+        interaction_id: Option<u64>,
+    ) -> Result<(), String> {
+        let round_id = self
+            .get_round_id(commitment)
+            .await
+            .map_err(|_| "ベットの受付に失敗しました".to_string())?;
+
+        reserve(
+            &self.conn,
+            UserId::Name(name.to_string()),
+            amount as i32,
+            self.table_id,
+            round_id,
+            interaction_id,
+        )
+        .await
+        .map_err(|why| match why {
+            ReserveError::InsufficientBalance => "残高が足りません".to_string(),
+            ReserveError::Duplicate => "このベットは既に受け付けています".to_string(),
+            ReserveError::Database(why) => {
+                println!("Cannot reserve the bet of {name}: {why}");
+                "ベットの受付に失敗しました".to_string()
+            }
+        })
+    }
+
+    async fn release(&mut self, commitment: &str, name: &str, amount: u32) {
+        let result = match self.get_round_id(commitment).await {
+            Ok(round_id) => {
+                release(
+                    &self.conn,
+                    UserId::Name(name.to_string()),
+                    amount as i32,
+                    self.table_id,
+                    round_id,
+                )
+                .await
+            }
+            Err(why) => Err(why),
+        };
+        if let Err(why) = result {
+            println!("Cannot release the bet of {name}: {why}");
+        }
+    }
+}
+
+fn parse_rules(options: &[CommandDataOption]) -> Result<TableRules, String> {
+    let mut rules = TableRules::default();
+
+    for option in options {
+        let invalid = || format!("{}の値が不正です", option.name);
+        let integer = || match option.value {
+            CommandDataOptionValue::Integer(value) => i32::try_from(value).map_err(|_| invalid()),
+            _ => Err(invalid()),
+        };
+        let positive = || integer().and_then(|value| u32::try_from(value).map_err(|_| invalid()));
+        let boolean = || option.value.as_bool().ok_or_else(invalid);
+        let string = || option.value.as_str().ok_or_else(invalid);
+
+        match option.name.as_str() {
+            "decks" => rules.decks = positive()?,
+            "penetration" => rules.penetration = positive()?,
+            "burn_card" => rules.burn_card = boolean()?,
+            "h17" => rules.dealer_hits_soft_17 = boolean()?,
+            "payout" => rules.blackjack_payout = string()?.parse()?,
+            "das" => rules.double_after_split = boolean()?,
+            "resplit" => rules.resplit = boolean()?,
+            "max_split_hands" => rules.max_split_hands = positive()? as usize,
+            "split_aces_one_card" => rules.split_aces_one_card = boolean()?,
+            "surrender" => rules.surrender = string()?.parse()?,
+            "min_bet" => rules.min_bet = positive()?,
+            "max_bet" => rules.max_bet = positive()?,
+            "betting_time" => rules.betting_time = positive()? as u64,
+            "turn_timeout" => rules.turn_timeout = positive()? as u64,
+            // ルールではなくテーブルの表示の設定なので、parse_card_styleで読む
+            "card_style" => {}
+            _ => return Err(invalid()),
+        }
+    }
 
-    let request = Request::new(res_tx, command);
+    rules.validate()?;
+    Ok(rules)
+}
 
-    game_tx.send(request).await.unwrap();
-    res_rx.await.unwrap().content
+// テーブルでのカードの表示形式。指定がなければコード
+fn parse_card_style(options: &[CommandDataOption]) -> Result<CardStyle, String> {
+    match options.iter().find(|option| option.name == "card_style") {
+        Some(option) => option
+            .value
+            .as_str()
+            .ok_or_else(|| format!("{}の値が不正です", option.name))?
+            .parse(),
+        None => Ok(CardStyle::Code),
+    }
 }
 
 impl Handler {
@@ -49,16 +253,38 @@ impl Handler {
         &self,
         http: Arc<serenity::http::Http>,
         channel_id: ChannelId,
+        user_id: u64,
+        rules: TableRules,
+        style: CardStyle,
     ) -> Result<CreateInteractionResponseMessage, String> {
         let channel_id_u64 = channel_id.get();
 
-        create_table(&self.conn, channel_id_u64)
+        create_table(&self.conn, channel_id_u64, user_id, &rules, style)
             .await
             .map_err(|_| "登録に失敗しました".to_string())?;
 
         let table_id = get_table_id(&self.conn, channel_id_u64)
             .await
             .map_err(|_| "テーブルIDの取得に失敗しました".to_string())?;
+        self.run_table(http, channel_id, table_id, rules.clone(), style, 0)
+            .await?;
+
+        Ok(CreateInteractionResponseMessage::new().content(format!(
+            "このチャンネルにゲームを登録しました（テーブルID: {}）\n{}\nカードの表示: {}",
+            table_id, rules, style
+        )))
+    }
+
+    // ゲームとメッセージを送るタスクを起動する。seqは次に記録するエフェクトの番号
+    async fn run_table(
+        &self,
+        http: Arc<serenity::http::Http>,
+        channel_id: ChannelId,
+        table_id: i32,
+        rules: TableRules,
+        style: CardStyle,
+        mut seq: i32,
+    ) -> Result<(), String> {
         let (game_tx, game_rx) = tokio::sync::mpsc::channel(1);
         if let std::collections::hash_map::Entry::Vacant(e) =
             self.game_txs.lock().unwrap().entry(table_id)
@@ -76,150 +302,295 @@ impl Handler {
         } else {
             return Err("このチャンネルには既にゲームが登録されています".to_string());
         };
-        {
+        let game_task = {
+            let rules = rules.clone();
             let broadcast_tx = broadcast_tx.clone();
-            tokio::spawn(game::run(game_rx, broadcast_tx.clone()));
-        }
+            let escrow = Box::new(DatabaseEscrow {
+                conn: self.conn.clone(),
+                table_id,
+            });
+            tokio::spawn(game::run(
+                game_rx,
+                broadcast_tx.clone(),
+                rules.clone(),
+                escrow,
+            ))
+        };
 
         {
             let remove_table_tx = self.remove_table_tx.clone();
+            let game_txs = self.game_txs.clone();
+            let broadcast_txs = self.broadcast_txs.clone();
+            let notice_http = http.clone();
             let conn = self.conn.clone();
+            let rules = rules.clone();
+            // 進行を表示するもの。Discord以外に表示する場合はここに加える
+            let mut observers: Vec<Box<dyn TableObserver>> = vec![Box::new(DiscordRenderer::new(
+                http,
+                channel_id,
+                table_id,
+                rules.clone(),
+                style,
+            ))];
             tokio::spawn(async move {
                 let mut state = state::State::new();
-                loop {
+                let mut round = RoundInfo {
+                    round_id: 0,
+                    settled: true,
+                };
+                // 作ったラウンドがまだ精算も無効化もされていないかどうか
+                let mut open = false;
+                // 記録に失敗して止めた場合はtrue
+                let halted = loop {
                     let effect = broadcast_rx.recv().await.unwrap();
+                    // 記録できなかったエフェクトの先には進まず、テーブルを止めて再起動後の復旧に任せる
+                    if let Err(why) = append_event_with_retry(&conn, table_id, seq, &effect).await {
+                        println!("Cannot append effect {seq} of table {table_id}: {why}");
+                        break true;
+                    }
+                    seq += 1;
                     state.apply_effect(effect.clone());
 
-                    match effect {
-                        Effect::Init(player_order) => {
-                            channel_id.say(&http, format!("{}秒後に次のゲームを始めます。参加・退室・ベットをしてください。", BETTING_TIME)).await.unwrap();
-                            channel_id
-                                .say(&http, format!("現在の参加者: {}", player_order.join(", ")))
-                                .await
-                                .unwrap();
+                    let round_id = round.round_id;
+                    match &effect {
+                        Effect::Commit(hash) => {
+                            // 配らずに終わったラウンドは無効にしておき、再起動のたびに残らないようにする
+                            if open {
+                                if let Err(why) = void_round(&conn, table_id, round_id).await {
+                                    println!("Cannot void round {round_id}: {why}");
+                                }
+                            }
+                            match retry(&format!("create a round of table {table_id}"), || {
+                                create_round(&conn, table_id, hash.clone())
+                            })
+                            .await
+                            {
+                                Ok(id) => round.round_id = id,
+                                Err(why) => {
+                                    println!("Cannot create a round of table {table_id}: {why}");
+                                    break true;
+                                }
+                            }
+                            open = true;
                         }
-                        Effect::AddPlayer(_) => {}
-                        Effect::RemovePlayer(_) => {}
-                        Effect::Bet(name, amount) => {
-                            bet(&conn, UserId::Name(name), amount as i32).await.unwrap()
+                        // シードとシューがなければ/verifyで再現できないので、記録できなければ止める
+                        Effect::RoundSeed(seed, cards) => {
+                            let shoe = cards
+                                .iter()
+                                .map(|card| card.to_string())
+                                .collect::<Vec<_>>()
+                                .join(" ");
+                            if let Err(why) = retry(&format!("start round {round_id}"), || {
+                                start_round(&conn, round_id, *seed, shoe.clone())
+                            })
+                            .await
+                            {
+                                println!("Cannot start round {round_id}: {why}");
+                                break true;
+                            }
                         }
-                        Effect::Deal(_, _) => {
-                            channel_id.say(&http, "カードを配布します。").await.unwrap();
-                            channel_id.say(&http, state.to_string()).await.unwrap();
+                        Effect::Reshuffle(_, cards) => {
+                            let shoe = cards
+                                .iter()
+                                .map(|card| card.to_string())
+                                .collect::<Vec<_>>()
+                                .join(" ");
+                            if let Err(why) = retry(&format!("reshuffle round {round_id}"), || {
+                                add_reshuffle(&conn, round_id, shoe.clone())
+                            })
+                            .await
+                            {
+                                println!("Cannot record the reshuffle of round {round_id}: {why}");
+                                break true;
+                            }
                         }
-                        Effect::DealerBlackjack => {
-                            channel_id
-                                .say(&http, "ディーラーがブラックジャックです。")
+                        Effect::Burn(card) => {
+                            if let Err(why) =
+                                retry(&format!("burn a card of round {round_id}"), || {
+                                    burn_card(&conn, round_id, card.to_string())
+                                })
                                 .await
-                                .unwrap();
+                            {
+                                println!("Cannot record the burn card of round {round_id}: {why}");
+                                break true;
+                            }
                         }
-                        Effect::Start => {
-                            channel_id.say(&http, "ゲームを開始します。").await.unwrap();
-                            channel_id
-                                .say(&http, "掛け金は以下のようになっています。")
-                                .await
-                                .unwrap();
-                            channel_id
-                                .say(
-                                    &http,
-                                    state
-                                        .get_amounts()
-                                        .iter()
-                                        .map(|(name, amount)| format!("{}: {}", name, amount))
-                                        .collect::<Vec<_>>()
-                                        .join("\n"),
+                        // 公開したシードを残せなければ検証できないので止める
+                        Effect::Reveal(server_seed, client_seed) => {
+                            if let Err(why) = retry(&format!("reveal round {round_id}"), || {
+                                reveal_round(
+                                    &conn,
+                                    round_id,
+                                    server_seed.clone(),
+                                    client_seed.clone(),
                                 )
-                                .await
-                                .unwrap();
-                        }
-                        Effect::AddCard(name, _) => {
-                            let player = state.get_player(&name).unwrap();
-                            channel_id.say(&http, format!("{}", player)).await.unwrap();
-                        }
-                        Effect::AddDealerCard(_) => {
-                            channel_id
-                                .say(&http, format!("{}", state.get_dealer()))
-                                .await
-                                .unwrap();
-                        }
-                        Effect::OpenDealerCard(_) => {
-                            channel_id
-                                .say(&http, format!("{}", state.get_dealer()))
-                                .await
-                                .unwrap();
-                        }
-                        Effect::Burst(name) => {
-                            channel_id
-                                .say(&http, format!("{name}さんはバーストしました。"))
-                                .await
-                                .unwrap();
-                        }
-                        Effect::DealerBurst => {
-                            channel_id
-                                .say(&http, "ディーラーがバーストしました。")
-                                .await
-                                .unwrap();
+                            })
+                            .await
+                            {
+                                println!("Cannot reveal round {round_id}: {why}");
+                                break true;
+                            }
                         }
-                        Effect::NextPlayer => match state.get_current_player() {
-                            Some(player) => {
-                                channel_id
-                                    .say(
-                                        &http,
-                                        format!(
-                                            "{}さんのターンです。コマンドを入力してください。",
-                                            player.name
-                                        ),
+                        // 返せなかった掛け金はベット中のまま残るので、照合か運営者が直せるように記録する
+                        Effect::Refund(name, amount) => {
+                            if let Err(why) = retry(
+                                &format!("release the bet of {name} in round {round_id}"),
+                                || {
+                                    release(
+                                        &conn,
+                                        UserId::Name(name.clone()),
+                                        *amount as i32,
+                                        table_id,
+                                        round_id,
                                     )
-                                    .await
-                                    .unwrap();
+                                },
+                            )
+                            .await
+                            {
+                                println!(
+                                    "Cannot release {amount} reserved for {name} in round {round_id}: {why}"
+                                );
                             }
-                            None => {
-                                channel_id
-                                    .say(&http, "ディーラーのターンです")
-                                    .await
-                                    .unwrap();
+                        }
+                        Effect::Close | Effect::NoPlayer if open => {
+                            if let Err(why) = void_round(&conn, table_id, round_id).await {
+                                println!("Cannot void round {round_id}: {why}");
                             }
-                        },
-                        Effect::NoPlayer => {
-                            channel_id
-                                .say(&http, "プレイヤーがいません。ゲームを終了します。")
-                                .await
-                                .unwrap();
-                            break;
+                            open = false;
                         }
                         Effect::Finish => {
-                            channel_id.say(&http, "ゲームが終了しました").await.unwrap();
-                            channel_id.say(&http, state.to_string()).await.unwrap();
-                            channel_id.say(&http, "結果を表示します").await.unwrap();
-                            let result = state.get_result();
-                            for (name, amount) in result.iter() {
-                                save_result(&conn, UserId::Name(name.clone()), amount.0 as i32)
+                            open = false;
+                            let record = RoundRecord::new(&state, &rules);
+                            round.settled =
+                                match settle_round_with_retry(&conn, table_id, round_id, &record)
                                     .await
-                                    .unwrap();
-                            }
-                            channel_id
-                                .say(
-                                    &http,
-                                    result
-                                        .iter()
-                                        .map(|(name, amount)| {
-                                            format!("{}: {} ({})", name, amount.0, amount.1)
-                                        })
-                                        .collect::<Vec<_>>()
-                                        .join("\n"),
-                                )
-                                .await
-                                .unwrap();
+                                {
+                                    Ok(()) => true,
+                                    Err(why) => {
+                                        println!("Cannot settle round {round_id}: {why}");
+                                        false
+                                    }
+                                };
+                        }
+                        _ => {}
+                    }
+
+                    for observer in observers.iter_mut() {
+                        observer.observe(&effect, &state, &round).await;
+                    }
+
+                    // ラウンドの最後の状態と閉じたことはすぐに表示し、それ以外は続けて届いたエフェクトをまとめてから表示する
+                    let closing = matches!(effect, Effect::Close | Effect::NoPlayer);
+                    if closing || effect == Effect::Finish || broadcast_rx.is_empty() {
+                        for observer in observers.iter_mut() {
+                            observer.flush(&state, &round).await;
                         }
                     }
-                }
 
-                remove_table_tx.send(table_id).await.unwrap();
+                    if closing {
+                        break false;
+                    }
+                };
+
+                if halted {
+                    // テーブルの行は残しておき、再起動後に中断したラウンドから復旧する
+                    game_task.abort();
+                    game_txs.lock().unwrap().remove(&table_id);
+                    broadcast_txs.lock().unwrap().remove(&table_id);
+                    if let Err(why) = channel_id
+                        .say(
+                            &notice_http,
+                            "ゲームの記録に失敗したため、テーブルを止めました。ボットの再起動後に再開します。",
+                        )
+                        .await
+                    {
+                        println!("Cannot send the notice to table {table_id}: {why}");
+                    }
+                } else {
+                    remove_table_tx.send(table_id).await.unwrap();
+                }
             });
         }
 
-        Ok(CreateInteractionResponseMessage::new()
-            .content("このチャンネルにゲームを登録しました".to_string()))
+        Ok(())
+    }
+
+    // 再起動前から残っているテーブルの中断されたラウンドを精算するか無効にして返金し、ゲームを再開する
+    async fn recover_tables(&self, http: Arc<serenity::http::Http>) {
+        let tables = match get_tables(&self.conn).await {
+            Ok(tables) => tables,
+            Err(why) => {
+                println!("Cannot get tables: {why}");
+                return;
+            }
+        };
+
+        for (table_id, channel_id, rules, style) in tables {
+            if self.game_txs.lock().unwrap().contains_key(&table_id) {
+                continue;
+            }
+            let channel_id = ChannelId::new(channel_id);
+
+            let mut notice = vec!["ボットが再起動したため、ゲームを再開します。".to_string()];
+            let rounds = match get_unsettled_rounds(&self.conn, table_id).await {
+                Ok(rounds) => rounds,
+                Err(why) => {
+                    println!("Cannot get unsettled rounds of table {table_id}: {why}");
+                    continue;
+                }
+            };
+            let seq = match get_next_seq(&self.conn, table_id).await {
+                Ok(seq) => seq,
+                Err(why) => {
+                    println!("Cannot get the events of table {table_id}: {why}");
+                    continue;
+                }
+            };
+            let effects = match get_effects(&self.conn, table_id, seq).await {
+                Ok(effects) => effects,
+                Err(why) => {
+                    println!("Cannot get the events of table {table_id}: {why}");
+                    continue;
+                }
+            };
+            // 結果が出たラウンドは無効にせず、記録したエフェクトから結果を再現して精算し直す
+            let mut finished = finished_rounds(effects, &rules);
+            for (round_id, commitment) in rounds {
+                if let Some(record) = finished.remove(&commitment) {
+                    match settle_round_with_retry(&self.conn, table_id, round_id, &record).await {
+                        Ok(()) => {
+                            notice.push(format!("中断されたラウンド{}を精算しました。", round_id))
+                        }
+                        Err(why) => println!("Cannot settle round {round_id}: {why}"),
+                    }
+                    continue;
+                }
+
+                match void_round(&self.conn, table_id, round_id).await {
+                    Ok(refunds) if refunds.is_empty() => {}
+                    Ok(refunds) => notice.push(format!(
+                        "中断されたラウンド{}を無効にし、掛け金を返金しました。\n{}",
+                        round_id,
+                        refunds
+                            .iter()
+                            .map(|(name, amount)| format!("{}: {}", name, amount))
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    )),
+                    Err(why) => println!("Cannot void round {round_id}: {why}"),
+                }
+            }
+
+            if let Err(why) = channel_id.say(&http, notice.join("\n")).await {
+                println!("Cannot post the recovery notice: {why}");
+            }
+            if let Err(why) = self
+                .run_table(http.clone(), channel_id, table_id, rules, style, seq)
+                .await
+            {
+                println!("Cannot resume table {table_id}: {why}");
+            }
+        }
     }
 
     async fn register_user(
@@ -238,9 +609,48 @@ impl Handler {
         let balance = get_balance(&self.conn, UserId::Discord(user_id))
             .await
             .map_err(|_| "残高の取得に失敗しました".to_string())?;
+        let reserved = get_reserved(&self.conn, UserId::Discord(user_id))
+            .await
+            .map_err(|_| "残高の取得に失敗しました".to_string())?;
+
+        Ok(CreateInteractionResponseMessage::new()
+            .content(format!("残高: {} (ベット中: {})", balance, reserved))
+            .ephemeral(true))
+    }
+
+    async fn history(&self, user_id: u64) -> Result<CreateInteractionResponseMessage, String> {
+        let history = get_history(&self.conn, UserId::Discord(user_id), 10)
+            .await
+            .map_err(|_| "取引履歴の取得に失敗しました".to_string())?;
+
+        if history.is_empty() {
+            return Err("取引履歴がありません".to_string());
+        }
+
+        let content = history
+            .iter()
+            .map(|transaction| {
+                let kind = match transaction.kind.parse() {
+                    Ok(TransactionKind::Opening) => "開始残高",
+                    Ok(TransactionKind::Bet) => "ベット",
+                    Ok(TransactionKind::Payout) => "配当",
+                    Ok(TransactionKind::Bonus) => "ボーナス",
+                    Ok(TransactionKind::Refund) => "返金",
+                    Err(_) => transaction.kind.as_str(),
+                };
+                match transaction.round_id {
+                    Some(round_id) => format!(
+                        "#{} {} {:+} (ラウンド{})",
+                        transaction.id, kind, transaction.amount, round_id
+                    ),
+                    None => format!("#{} {} {:+}", transaction.id, kind, transaction.amount),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
 
         Ok(CreateInteractionResponseMessage::new()
-            .content(format!("残高: {}", balance))
+            .content(content)
             .ephemeral(true))
     }
 
@@ -324,10 +734,16 @@ impl Handler {
 
     async fn bet(
         &self,
+        interaction_id: u64,
         channel_id: u64,
         user_id: u64,
-        amount: i32,
+        amount: i64,
     ) -> Result<CreateInteractionResponseMessage, String> {
+        if amount <= 0 {
+            return Err("1コイン以上を指定してください".to_string());
+        }
+        let amount = i32::try_from(amount).map_err(|_| "金額が大きすぎます".to_string())? as u32;
+
         let table_id = get_table_id(&self.conn, channel_id)
             .await
             .map_err(|_| "テーブルIDの取得に失敗しました".to_string())?;
@@ -344,7 +760,9 @@ impl Handler {
             .await
             .map_err(|_| "ユーザーが見つかりませんでした。登録してください。".to_string())?;
 
-        let content = exec_game_command(game_tx, Command::Bet(name, amount as u32)).await;
+        // 掛け金を取り置けたときだけインタラクションを処理済みにする
+        let content =
+            exec_game_command_with_id(game_tx, Command::Bet(name, amount), interaction_id).await;
 
         Ok(CreateInteractionResponseMessage::new().content(content))
     }
@@ -401,6 +819,450 @@ impl Handler {
         Ok(CreateInteractionResponseMessage::new().content(content))
     }
 
+    async fn double_down(
+        &self,
+        channel_id: u64,
+        user_id: u64,
+    ) -> Result<CreateInteractionResponseMessage, String> {
+        let table_id = get_table_id(&self.conn, channel_id)
+            .await
+            .map_err(|_| "テーブルIDの取得に失敗しました".to_string())?;
+
+        let game_tx = self
+            .game_txs
+            .lock()
+            .unwrap()
+            .get(&table_id)
+            .ok_or("このチャンネルにはゲームが登録されていません".to_string())?
+            .clone();
+
+        let name = get_username_by_discord(&self.conn, user_id)
+            .await
+            .map_err(|_| "ユーザーが見つかりませんでした。登録してください。".to_string())?;
+
+        let content = exec_game_command(game_tx, Command::DoubleDown(name)).await;
+
+        Ok(CreateInteractionResponseMessage::new().content(content))
+    }
+
+    async fn split(
+        &self,
+        channel_id: u64,
+        user_id: u64,
+    ) -> Result<CreateInteractionResponseMessage, String> {
+        let table_id = get_table_id(&self.conn, channel_id)
+            .await
+            .map_err(|_| "テーブルIDの取得に失敗しました".to_string())?;
+
+        let game_tx = self
+            .game_txs
+            .lock()
+            .unwrap()
+            .get(&table_id)
+            .ok_or("このチャンネルにはゲームが登録されていません".to_string())?
+            .clone();
+
+        let name = get_username_by_discord(&self.conn, user_id)
+            .await
+            .map_err(|_| "ユーザーが見つかりませんでした。登録してください。".to_string())?;
+
+        let content = exec_game_command(game_tx, Command::Split(name)).await;
+
+        Ok(CreateInteractionResponseMessage::new().content(content))
+    }
+
+    async fn insurance(
+        &self,
+        channel_id: u64,
+        user_id: u64,
+    ) -> Result<CreateInteractionResponseMessage, String> {
+        let table_id = get_table_id(&self.conn, channel_id)
+            .await
+            .map_err(|_| "テーブルIDの取得に失敗しました".to_string())?;
+
+        let game_tx = self
+            .game_txs
+            .lock()
+            .unwrap()
+            .get(&table_id)
+            .ok_or("このチャンネルにはゲームが登録されていません".to_string())?
+            .clone();
+
+        let name = get_username_by_discord(&self.conn, user_id)
+            .await
+            .map_err(|_| "ユーザーが見つかりませんでした。登録してください。".to_string())?;
+
+        let content = exec_game_command(game_tx, Command::Insurance(name)).await;
+
+        Ok(CreateInteractionResponseMessage::new().content(content))
+    }
+
+    async fn even_money(
+        &self,
+        channel_id: u64,
+        user_id: u64,
+    ) -> Result<CreateInteractionResponseMessage, String> {
+        let table_id = get_table_id(&self.conn, channel_id)
+            .await
+            .map_err(|_| "テーブルIDの取得に失敗しました".to_string())?;
+
+        let game_tx = self
+            .game_txs
+            .lock()
+            .unwrap()
+            .get(&table_id)
+            .ok_or("このチャンネルにはゲームが登録されていません".to_string())?
+            .clone();
+
+        let name = get_username_by_discord(&self.conn, user_id)
+            .await
+            .map_err(|_| "ユーザーが見つかりませんでした。登録してください。".to_string())?;
+
+        let content = exec_game_command(game_tx, Command::EvenMoney(name)).await;
+
+        Ok(CreateInteractionResponseMessage::new().content(content))
+    }
+
+    async fn ready(
+        &self,
+        channel_id: u64,
+        user_id: u64,
+    ) -> Result<CreateInteractionResponseMessage, String> {
+        let table_id = get_table_id(&self.conn, channel_id)
+            .await
+            .map_err(|_| "テーブルIDの取得に失敗しました".to_string())?;
+
+        let game_tx = self
+            .game_txs
+            .lock()
+            .unwrap()
+            .get(&table_id)
+            .ok_or("このチャンネルにはゲームが登録されていません".to_string())?
+            .clone();
+
+        let name = get_username_by_discord(&self.conn, user_id)
+            .await
+            .map_err(|_| "ユーザーが見つかりませんでした。登録してください。".to_string())?;
+
+        let content = exec_game_command(game_tx, Command::Ready(name)).await;
+
+        Ok(CreateInteractionResponseMessage::new().content(content))
+    }
+
+    // テーブルの作成者か管理権限のあるユーザーのみがテーブルを操作できる
+    async fn authorize_table(
+        &self,
+        table_id: i32,
+        user_id: u64,
+        manager: bool,
+    ) -> Result<(), String> {
+        if manager {
+            return Ok(());
+        }
+
+        let creator_id = get_table_creator(&self.conn, table_id)
+            .await
+            .map_err(|_| "テーブルの取得に失敗しました".to_string())?;
+        if creator_id != Some(user_id) {
+            return Err("テーブルを操作できるのは作成者と管理者のみです".to_string());
+        }
+
+        Ok(())
+    }
+
+    async fn stop(
+        &self,
+        channel_id: u64,
+        user_id: u64,
+        manager: bool,
+    ) -> Result<CreateInteractionResponseMessage, String> {
+        let table_id = get_table_id(&self.conn, channel_id)
+            .await
+            .map_err(|_| "テーブルIDの取得に失敗しました".to_string())?;
+
+        self.authorize_table(table_id, user_id, manager).await?;
+
+        let game_tx = self
+            .game_txs
+            .lock()
+            .unwrap()
+            .get(&table_id)
+            .ok_or("このチャンネルにはゲームが登録されていません".to_string())?
+            .clone();
+
+        let name = get_username_by_discord(&self.conn, user_id)
+            .await
+            .map_err(|_| "ユーザーが見つかりませんでした。登録してください。".to_string())?;
+
+        let content = exec_game_command(game_tx, Command::Stop(name)).await;
+
+        Ok(CreateInteractionResponseMessage::new().content(content))
+    }
+
+    async fn pause(
+        &self,
+        channel_id: u64,
+        user_id: u64,
+        manager: bool,
+    ) -> Result<CreateInteractionResponseMessage, String> {
+        let table_id = get_table_id(&self.conn, channel_id)
+            .await
+            .map_err(|_| "テーブルIDの取得に失敗しました".to_string())?;
+
+        self.authorize_table(table_id, user_id, manager).await?;
+
+        let game_tx = self
+            .game_txs
+            .lock()
+            .unwrap()
+            .get(&table_id)
+            .ok_or("このチャンネルにはゲームが登録されていません".to_string())?
+            .clone();
+
+        let name = get_username_by_discord(&self.conn, user_id)
+            .await
+            .map_err(|_| "ユーザーが見つかりませんでした。登録してください。".to_string())?;
+
+        let content = exec_game_command(game_tx, Command::Pause(name)).await;
+
+        Ok(CreateInteractionResponseMessage::new().content(content))
+    }
+
+    async fn resume(
+        &self,
+        channel_id: u64,
+        user_id: u64,
+        manager: bool,
+    ) -> Result<CreateInteractionResponseMessage, String> {
+        let table_id = get_table_id(&self.conn, channel_id)
+            .await
+            .map_err(|_| "テーブルIDの取得に失敗しました".to_string())?;
+
+        self.authorize_table(table_id, user_id, manager).await?;
+
+        let game_tx = self
+            .game_txs
+            .lock()
+            .unwrap()
+            .get(&table_id)
+            .ok_or("このチャンネルにはゲームが登録されていません".to_string())?
+            .clone();
+
+        let name = get_username_by_discord(&self.conn, user_id)
+            .await
+            .map_err(|_| "ユーザーが見つかりませんでした。登録してください。".to_string())?;
+
+        let content = exec_game_command(game_tx, Command::Resume(name)).await;
+
+        Ok(CreateInteractionResponseMessage::new().content(content))
+    }
+
+    async fn surrender(
+        &self,
+        channel_id: u64,
+        user_id: u64,
+    ) -> Result<CreateInteractionResponseMessage, String> {
+        let table_id = get_table_id(&self.conn, channel_id)
+            .await
+            .map_err(|_| "テーブルIDの取得に失敗しました".to_string())?;
+
+        let game_tx = self
+            .game_txs
+            .lock()
+            .unwrap()
+            .get(&table_id)
+            .ok_or("このチャンネルにはゲームが登録されていません".to_string())?
+            .clone();
+
+        let name = get_username_by_discord(&self.conn, user_id)
+            .await
+            .map_err(|_| "ユーザーが見つかりませんでした。登録してください。".to_string())?;
+
+        let content = exec_game_command(game_tx, Command::Surrender(name)).await;
+
+        Ok(CreateInteractionResponseMessage::new().content(content))
+    }
+
+    async fn client_seed(
+        &self,
+        channel_id: u64,
+        user_id: u64,
+        seed: String,
+    ) -> Result<CreateInteractionResponseMessage, String> {
+        let table_id = get_table_id(&self.conn, channel_id)
+            .await
+            .map_err(|_| "テーブルIDの取得に失敗しました".to_string())?;
+
+        let game_tx = self
+            .game_txs
+            .lock()
+            .unwrap()
+            .get(&table_id)
+            .ok_or("このチャンネルにはゲームが登録されていません".to_string())?
+            .clone();
+
+        let name = get_username_by_discord(&self.conn, user_id)
+            .await
+            .map_err(|_| "ユーザーが見つかりませんでした。登録してください。".to_string())?;
+
+        let content = exec_game_command(game_tx, Command::ClientSeed(name, seed)).await;
+
+        Ok(CreateInteractionResponseMessage::new().content(content))
+    }
+
+    async fn verify(&self, round_id: i32) -> Result<CreateInteractionResponseMessage, String> {
+        let round = get_round(&self.conn, round_id)
+            .await
+            .map_err(|_| "ラウンドが見つかりませんでした".to_string())?;
+
+        let (server_seed, client_seed, shoe) =
+            match (round.server_seed, round.client_seed, round.shoe) {
+                (Some(server_seed), Some(client_seed), Some(shoe)) => {
+                    (server_seed, client_seed, shoe)
+                }
+                _ => return Err("このラウンドのシードはまだ公開されていません".to_string()),
+            };
+
+        let order = game::fair::verify(
+            &round.commitment,
+            &server_seed,
+            &client_seed,
+            &shoe,
+            &round.reshuffles,
+        )?;
+
+        // シュー全体はメッセージに収まらないので、1行に1枚ずつファイルに書いて添える
+        let count = order.len();
+        let mut reshuffled_at = vec![];
+        let mut position = shoe.split_whitespace().count();
+        for reshuffle in round.reshuffles.iter() {
+            reshuffled_at.push(position);
+            position += reshuffle.split_whitespace().count();
+        }
+        let mut lines = vec![];
+        for (index, card) in order.iter().enumerate() {
+            if reshuffled_at.contains(&index) {
+                lines.push("-- シャッフルし直し --".to_string());
+            }
+            if index == 0 && round.burn_card.as_ref() == Some(card) {
+                lines.push(format!("{} {} (バーンカード)", index + 1, card));
+            } else {
+                lines.push(format!("{} {}", index + 1, card));
+            }
+        }
+        let file = CreateAttachment::bytes(lines.join("\n"), format!("round{round_id}.txt"));
+
+        let burn = match round.burn_card {
+            Some(card) => format!("\n最初の1枚（{card}）はバーンカードとして捨てました。"),
+            None => String::new(),
+        };
+        Ok(CreateInteractionResponseMessage::new()
+            .content(format!(
+                "ハッシュが一致しました。\nハッシュ: `{}`\nサーバーシード: `{}`\nクライアントシード: `{}`\n配られる順番（全{}枚）は添付ファイルの通りです。{}",
+                round.commitment, server_seed, client_seed, count, burn
+            ))
+            .add_file(file))
+    }
+
+    // ユーザーが選んだカードの表示形式。選んでいなければ既定の形式
+    async fn card_style(&self, user_id: u64) -> CardStyle {
+        get_card_style(&self.conn, user_id)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    async fn set_card_style(
+        &self,
+        user_id: u64,
+        style: CardStyle,
+    ) -> Result<CreateInteractionResponseMessage, String> {
+        set_card_style(&self.conn, user_id, style)
+            .await
+            .map_err(|_| "ユーザーが見つかりませんでした。登録してください。".to_string())?;
+
+        let example = "SA".parse::<Card>().unwrap().format(style);
+        Ok(CreateInteractionResponseMessage::new()
+            .content(format!("カードの表示を{example}のような形式にしました"))
+            .ephemeral(true))
+    }
+
+    async fn hand(
+        &self,
+        user_id: u64,
+        round_id: i32,
+    ) -> Result<CreateInteractionResponseMessage, String> {
+        let record = get_round_record(&self.conn, round_id)
+            .await
+            .map_err(|_| "ラウンドが見つかりませんでした".to_string())?;
+        let state = record.replay()?.format(self.card_style(user_id).await);
+
+        let result = record
+            .players
+            .iter()
+            .map(|player| format!("{}: {} ({})", player.name, player.payout, player.net))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Ok(CreateInteractionResponseMessage::new()
+            .content(format!("ラウンド{}\n{}\n{}", round_id, state, result)))
+    }
+
+    // テーブルのseq番目のエフェクトまでを適用した状態と、そこまでの出来事を表示する
+    async fn replay(
+        &self,
+        user_id: u64,
+        table_id: i32,
+        seq: i32,
+    ) -> Result<CreateInteractionResponseMessage, String> {
+        let effects = get_effects(&self.conn, table_id, seq)
+            .await
+            .map_err(|_| "記録の取得に失敗しました".to_string())?;
+        if effects.is_empty() {
+            return Err("記録が見つかりませんでした".to_string());
+        }
+        // 閉じたテーブルのルールは残っていないので既定のルールで表示する
+        let rules = get_tables(&self.conn)
+            .await
+            .ok()
+            .and_then(|tables| tables.into_iter().find(|(id, _, _, _)| *id == table_id))
+            .map(|(_, _, rules, _)| rules)
+            .unwrap_or_default();
+        let style = self.card_style(user_id).await;
+        let mut renderer = TextRenderer::new(rules, style);
+
+        let count = effects.len();
+        let mut state = state::State::new();
+        let mut round = RoundInfo {
+            round_id: 0,
+            settled: true,
+        };
+        for effect in effects {
+            // ラウンドはCommitのコミットメントで探す
+            if let Effect::Commit(hash) = &effect {
+                if let Some((round_id, settled)) = find_round(&self.conn, hash)
+                    .await
+                    .map_err(|_| "記録の取得に失敗しました".to_string())?
+                {
+                    round = RoundInfo { round_id, settled };
+                }
+            }
+            state.apply_effect(effect.clone());
+            renderer.observe(&effect, &state, &round).await;
+        }
+        let lines = renderer.get_lines();
+        let recent = &lines[lines.len().saturating_sub(REPLAY_LINES)..];
+
+        Ok(CreateInteractionResponseMessage::new().content(format!(
+            "テーブル{}の{}個目のエフェクトまでの状態\n{}\n{}",
+            table_id,
+            count,
+            state.format(style),
+            recent.join("\n")
+        )))
+    }
+
     async fn create_bonus(&self, amount: i32) -> Result<CreateInteractionResponseMessage, String> {
         let result = create_bonus(&self.conn, amount).await;
 
@@ -426,6 +1288,33 @@ impl Handler {
             Err(_) => Err("ボーナスの取得に失敗しました".to_string()),
         }
     }
+
+    // ターンのメッセージに付けた行動ボタンを押したとき
+    async fn press_button(
+        &self,
+        channel_id: u64,
+        user_id: u64,
+        custom_id: &str,
+    ) -> Result<CreateInteractionResponseMessage, String> {
+        let (action, player) = custom_id
+            .split_once(':')
+            .ok_or("未知のボタン".to_string())?;
+
+        let name = get_username_by_discord(&self.conn, user_id)
+            .await
+            .map_err(|_| "ユーザーが見つかりませんでした。登録してください。".to_string())?;
+        if name != player {
+            return Err("あなたのターンではありません".to_string());
+        }
+
+        match action {
+            "hit" => self.hit(channel_id, user_id).await,
+            "stand" => self.stand(channel_id, user_id).await,
+            "double" => self.double_down(channel_id, user_id).await,
+            "split" => self.split(channel_id, user_id).await,
+            _ => Err("未知のボタン".to_string()),
+        }
+    }
 }
 
 #[async_trait]
@@ -434,25 +1323,80 @@ impl EventHandler for Handler {
         if let Interaction::Command(command) = interaction {
             let user_id = command.user.id.get();
             let channel_id = command.channel_id.get();
+            // チャンネルの管理権限があればテーブルを操作できる
+            let manager = command
+                .member
+                .as_ref()
+                .and_then(|member| member.permissions)
+                .is_some_and(|permissions| {
+                    permissions.manage_channels() || permissions.manage_guild()
+                });
 
             let result = match command.data.name.as_str() {
-                "start" => self.start(ctx.http.clone(), command.channel_id).await,
+                "start" => match (
+                    parse_rules(&command.data.options),
+                    parse_card_style(&command.data.options),
+                ) {
+                    (Ok(rules), Ok(style)) => {
+                        self.start(ctx.http.clone(), command.channel_id, user_id, rules, style)
+                            .await
+                    }
+                    (Err(err), _) | (_, Err(err)) => Err(err),
+                },
                 "ping" => self.ping(channel_id, user_id).await,
+                "stop" => self.stop(channel_id, user_id, manager).await,
+                "pause" => self.pause(channel_id, user_id, manager).await,
+                "resume" => self.resume(channel_id, user_id, manager).await,
                 "register" => {
                     let name = &command.data.options.first().unwrap().value;
                     let name = name.as_str().unwrap().to_string();
                     self.register_user(user_id, name).await
                 }
                 "balance" => self.get_balance(user_id).await,
+                "history" => self.history(user_id).await,
+                "card_style" => {
+                    let style = &command.data.options.first().unwrap().value;
+                    match style.as_str().unwrap().parse() {
+                        Ok(style) => self.set_card_style(user_id, style).await,
+                        Err(err) => Err(err),
+                    }
+                }
                 "participate" => self.participate(channel_id, user_id).await,
                 "leave" => self.leave(channel_id, user_id).await,
                 "bet" => {
                     let amount = &command.data.options.first().unwrap().value;
                     let amount = amount.as_i64().unwrap();
-                    self.bet(channel_id, user_id, amount as i32).await
+                    self.bet(command.id.get(), channel_id, user_id, amount)
+                        .await
                 }
+                "ready" => self.ready(channel_id, user_id).await,
                 "hit" => self.hit(channel_id, user_id).await,
                 "stand" => self.stand(channel_id, user_id).await,
+                "double" => self.double_down(channel_id, user_id).await,
+                "split" => self.split(channel_id, user_id).await,
+                "insurance" => self.insurance(channel_id, user_id).await,
+                "even_money" => self.even_money(channel_id, user_id).await,
+                "surrender" => self.surrender(channel_id, user_id).await,
+                "client_seed" => {
+                    let seed = &command.data.options.first().unwrap().value;
+                    let seed = seed.as_str().unwrap().to_string();
+                    self.client_seed(channel_id, user_id, seed).await
+                }
+                "verify" => {
+                    let round_id = &command.data.options.first().unwrap().value;
+                    let round_id = round_id.as_i64().unwrap();
+                    self.verify(round_id as i32).await
+                }
+                "hand" => {
+                    let round_id = &command.data.options.first().unwrap().value;
+                    let round_id = round_id.as_i64().unwrap();
+                    self.hand(user_id, round_id as i32).await
+                }
+                "replay" => {
+                    let table_id = command.data.options[0].value.as_i64().unwrap();
+                    let seq = command.data.options[1].value.as_i64().unwrap();
+                    self.replay(user_id, table_id as i32, seq as i32).await
+                }
                 "create_bonus" => {
                     let amount = &command.data.options.first().unwrap().value;
                     let amount = amount.as_i64().unwrap();
@@ -477,6 +1421,26 @@ impl EventHandler for Handler {
             if let Err(why) = command.create_response(&ctx.http, builder).await {
                 println!("Cannot respond to slash command: {why}");
             }
+        } else if let Interaction::Component(component) = interaction {
+            let user_id = component.user.id.get();
+            let channel_id = component.channel_id.get();
+
+            let result = self
+                .press_button(channel_id, user_id, &component.data.custom_id)
+                .await;
+
+            // 盤面は埋め込みに反映されるので、結果は押した人にだけ返す
+            let data = match result {
+                Ok(content) => content.ephemeral(true),
+                Err(content) => CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            };
+
+            let builder = CreateInteractionResponse::Message(data);
+            if let Err(why) = component.create_response(&ctx.http, builder).await {
+                println!("Cannot respond to button: {why}");
+            }
         }
     }
 
@@ -490,8 +1454,102 @@ impl EventHandler for Handler {
                 .set_commands(
                     &ctx.http,
                     vec![
-                        CreateCommand::new("start").description("チャンネルを登録"),
+                        CreateCommand::new("start")
+                            .description("チャンネルを登録")
+                            .add_option(CreateCommandOption::new(
+                                CommandOptionType::Integer,
+                                "decks",
+                                "デッキ数",
+                            ))
+                            .add_option(CreateCommandOption::new(
+                                CommandOptionType::Integer,
+                                "penetration",
+                                "カットカードまでに配る割合（%）",
+                            ))
+                            .add_option(CreateCommandOption::new(
+                                CommandOptionType::Boolean,
+                                "burn_card",
+                                "シャッフル後にカードを1枚捨てる",
+                            ))
+                            .add_option(CreateCommandOption::new(
+                                CommandOptionType::Boolean,
+                                "h17",
+                                "ディーラーがソフト17でヒットする",
+                            ))
+                            .add_option(
+                                CreateCommandOption::new(
+                                    CommandOptionType::String,
+                                    "payout",
+                                    "ブラックジャックの配当",
+                                )
+                                .add_string_choice("3:2", "3:2")
+                                .add_string_choice("6:5", "6:5"),
+                            )
+                            .add_option(CreateCommandOption::new(
+                                CommandOptionType::Boolean,
+                                "das",
+                                "スプリット後のダブルダウン",
+                            ))
+                            .add_option(CreateCommandOption::new(
+                                CommandOptionType::Boolean,
+                                "resplit",
+                                "リスプリット",
+                            ))
+                            .add_option(CreateCommandOption::new(
+                                CommandOptionType::Integer,
+                                "max_split_hands",
+                                "スプリットで作れるハンドの最大数",
+                            ))
+                            .add_option(CreateCommandOption::new(
+                                CommandOptionType::Boolean,
+                                "split_aces_one_card",
+                                "エースのスプリットは1枚のみ",
+                            ))
+                            .add_option(
+                                CreateCommandOption::new(
+                                    CommandOptionType::String,
+                                    "surrender",
+                                    "サレンダー",
+                                )
+                                .add_string_choice("なし", "none")
+                                .add_string_choice("レイト", "late")
+                                .add_string_choice("アーリー", "early"),
+                            )
+                            .add_option(CreateCommandOption::new(
+                                CommandOptionType::Integer,
+                                "min_bet",
+                                "最低ベット",
+                            ))
+                            .add_option(CreateCommandOption::new(
+                                CommandOptionType::Integer,
+                                "max_bet",
+                                "最高ベット",
+                            ))
+                            .add_option(CreateCommandOption::new(
+                                CommandOptionType::Integer,
+                                "betting_time",
+                                "ベット受付時間（秒）",
+                            ))
+                            .add_option(CreateCommandOption::new(
+                                CommandOptionType::Integer,
+                                "turn_timeout",
+                                "ターンの制限時間（秒）",
+                            ))
+                            .add_option(
+                                CreateCommandOption::new(
+                                    CommandOptionType::String,
+                                    "card_style",
+                                    "カードの表示",
+                                )
+                                .add_string_choice("コード（SA）", "code")
+                                .add_string_choice("記号（♠A）", "symbol")
+                                .add_string_choice("トランプ（🂡）", "unicode")
+                                .add_string_choice("絵文字（♠️🇦）", "emoji"),
+                            ),
                         CreateCommand::new("ping").description("テスト用"),
+                        CreateCommand::new("stop").description("ラウンドの後にテーブルを閉じる"),
+                        CreateCommand::new("pause").description("一時停止"),
+                        CreateCommand::new("resume").description("再開"),
                         CreateCommand::new("register")
                             .description("登録")
                             .add_option(
@@ -499,14 +1557,83 @@ impl EventHandler for Handler {
                                     .required(true),
                             ),
                         CreateCommand::new("balance").description("残高"),
+                        CreateCommand::new("history").description("取引履歴"),
+                        CreateCommand::new("card_style")
+                            .description("カードの表示を設定")
+                            .add_option(
+                                CreateCommandOption::new(
+                                    CommandOptionType::String,
+                                    "style",
+                                    "表示",
+                                )
+                                .add_string_choice("コード（SA）", "code")
+                                .add_string_choice("記号（♠A）", "symbol")
+                                .add_string_choice("トランプ（🂡）", "unicode")
+                                .add_string_choice("絵文字（♠️🇦）", "emoji")
+                                .required(true),
+                            ),
                         CreateCommand::new("participate").description("参加"),
                         CreateCommand::new("leave").description("退室"),
                         CreateCommand::new("bet").description("ベット").add_option(
                             CreateCommandOption::new(CommandOptionType::Integer, "amount", "金額")
                                 .required(true),
                         ),
+                        CreateCommand::new("ready").description("準備完了"),
                         CreateCommand::new("hit").description("ヒット"),
                         CreateCommand::new("stand").description("スタンド"),
+                        CreateCommand::new("double").description("ダブルダウン"),
+                        CreateCommand::new("split").description("スプリット"),
+                        CreateCommand::new("insurance").description("インシュランス"),
+                        CreateCommand::new("even_money").description("イーブンマネー"),
+                        CreateCommand::new("surrender").description("サレンダー"),
+                        CreateCommand::new("client_seed")
+                            .description("クライアントシードを設定")
+                            .add_option(
+                                CreateCommandOption::new(
+                                    CommandOptionType::String,
+                                    "seed",
+                                    "シード",
+                                )
+                                .required(true),
+                            ),
+                        CreateCommand::new("verify")
+                            .description("シャッフルを検証")
+                            .add_option(
+                                CreateCommandOption::new(
+                                    CommandOptionType::Integer,
+                                    "id",
+                                    "ラウンドID",
+                                )
+                                .required(true),
+                            ),
+                        CreateCommand::new("hand")
+                            .description("ラウンドの記録を表示")
+                            .add_option(
+                                CreateCommandOption::new(
+                                    CommandOptionType::Integer,
+                                    "id",
+                                    "ラウンドID",
+                                )
+                                .required(true),
+                            ),
+                        CreateCommand::new("replay")
+                            .description("テーブルの記録を再現")
+                            .add_option(
+                                CreateCommandOption::new(
+                                    CommandOptionType::Integer,
+                                    "table",
+                                    "テーブルID",
+                                )
+                                .required(true),
+                            )
+                            .add_option(
+                                CreateCommandOption::new(
+                                    CommandOptionType::Integer,
+                                    "seq",
+                                    "エフェクトの番号",
+                                )
+                                .required(true),
+                            ),
                         CreateCommand::new("create_bonus")
                             .description("ボーナス追加")
                             .add_option(
@@ -531,6 +1658,8 @@ impl EventHandler for Handler {
                 println!("Cannot create slash commands: {why}");
             }
         }
+
+        self.recover_tables(ctx.http.clone()).await;
     }
 }
 
@@ -540,6 +1669,16 @@ async fn main() {
 
     let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
     let conn = database::establish_connection().await.unwrap();
+    for discrepancy in reconcile_balances(&conn).await.unwrap() {
+        println!(
+            "Reconciled the balance of {}: {}/{} -> {}/{}",
+            discrepancy.name,
+            discrepancy.balance,
+            discrepancy.reserved,
+            discrepancy.ledger_balance,
+            discrepancy.ledger_reserved
+        );
+    }
     let (remove_table_tx, mut remove_table_rx) = mpsc::channel(1);
     let handler = Arc::new(Handler {
         game_txs: Arc::new(Mutex::new(HashMap::new())),
@@ -0,0 +1,225 @@
+use async_trait::async_trait;
+
+use crate::game::card::CardStyle;
+use crate::game::rules::TableRules;
+use crate::game::state::{Effect, State};
+use crate::game::{SKIP_LIMIT, TIMEOUT_LIMIT};
+
+// 盤面以外で表示に必要なラウンドの情報
+pub struct RoundInfo {
+    pub round_id: i32,
+    // 精算できたかどうか
+    pub settled: bool,
+}
+
+// テーブルの進行を表示するもの。エフェクトと、それを適用した後の状態を受け取る
+#[async_trait]
+pub trait TableObserver: Send {
+    async fn observe(&mut self, effect: &Effect, state: &State, round: &RoundInfo);
+
+    // 続けて届いたエフェクトを受け取り終えたときに呼ばれる。表示をまとめて更新する場合に使う
+    async fn flush(&mut self, _state: &State, _round: &RoundInfo) {}
+}
+
+// エフェクトで起きた出来事の説明。盤面から分かることは含めない
+pub fn describe(effect: &Effect) -> Vec<String> {
+    let line = match effect {
+        Effect::Shuffle => "シューをシャッフルしました。".to_string(),
+        // 捨てたカードはラウンドの後に/verifyで確認できる
+        Effect::Burn(_) => "最初の1枚をバーンカードとして捨てました。".to_string(),
+        Effect::Reshuffle(..) => {
+            "シューが尽きたため、場に出ていないカードをシャッフルし直しました。".to_string()
+        }
+        Effect::ClientSeed(name, seed) => {
+            format!("{name}さんがクライアントシードを`{seed}`に設定しました。")
+        }
+        Effect::ExtendBetting(seconds) => {
+            format!("参加者が増えたため、ベットの受付を{seconds}秒延長します。")
+        }
+        Effect::SitOut(name, count) => {
+            let mut lines =
+                vec![format!("{name}さんはベットしていないため、このラウンドを見送ります。")];
+            if *count >= SKIP_LIMIT {
+                lines.push(format!(
+                    "見送りが続いたため、{name}さんは次のラウンドから席を外れます。"
+                ));
+            }
+            return lines;
+        }
+        Effect::DealerBlackjack => "ディーラーがブラックジャックです。".to_string(),
+        Effect::OfferInsurance => "ディーラーのアップカードがエースです。インシュランス（ブラックジャックの場合はイーブンマネー）を選択できます。".to_string(),
+        Effect::OfferSurrender => {
+            "ディーラーがブラックジャックを確認する前にサレンダーできます。".to_string()
+        }
+        Effect::Insurance(name, amount) => {
+            format!("{name}さんが{amount}コインのインシュランスをかけました。")
+        }
+        Effect::EvenMoney(name) => format!("{name}さんがイーブンマネーを選びました。"),
+        Effect::CloseInsurance => "ディーラーはブラックジャックではありませんでした。".to_string(),
+        Effect::DoubleDown(name, amount) => {
+            format!("{name}さんが{amount}コインを追加で賭けました。")
+        }
+        Effect::Split(name, amount) => {
+            format!("{name}さんがスプリットして{amount}コインを追加で賭けました。")
+        }
+        Effect::Surrender(name) => format!("{name}さんがサレンダーしました。"),
+        Effect::Burst(name) => format!("{name}さんはバーストしました。"),
+        Effect::DealerBurst => "ディーラーがバーストしました。".to_string(),
+        Effect::TurnWarning(name, seconds) => format!("{name}さん、残り{seconds}秒です。"),
+        Effect::TimedOut(name, count) => {
+            let mut lines = vec![format!("{name}さんは時間切れのためスタンドしました。")];
+            if *count >= TIMEOUT_LIMIT {
+                lines.push(format!(
+                    "時間切れが続いたため、{name}さんは次のラウンドから席を外れます。"
+                ));
+            }
+            return lines;
+        }
+        Effect::Pause => "ゲームを一時停止しました。".to_string(),
+        Effect::Resume => "ゲームを再開しました。".to_string(),
+        Effect::Stop => "このラウンドの後にテーブルを閉じます。".to_string(),
+        Effect::Close => "テーブルを閉じました。".to_string(),
+        Effect::NoPlayer => "プレイヤーがいません。ゲームを終了します。".to_string(),
+        _ => return vec![],
+    };
+    vec![line]
+}
+
+// ラウンドの結果とシードの説明
+pub fn result_lines(
+    state: &State,
+    rules: &TableRules,
+    round: &RoundInfo,
+    seeds: (&str, &str),
+) -> Vec<String> {
+    let round_id = round.round_id;
+    let result = state.get_result(rules);
+    let mut lines = vec![format!("ラウンド{round_id}の結果")];
+    if !round.settled {
+        lines.push("精算に失敗しました。掛け金はベット中のまま保留されています。".to_string());
+    }
+    for name in state.get_player_order() {
+        let (payout, net) = result[&name];
+        lines.push(format!("{name}: {payout} ({net:+})"));
+    }
+
+    let (server_seed, client_seed) = seeds;
+    lines.push(format!("サーバーシード: `{server_seed}`"));
+    lines.push(format!("クライアントシード: `{client_seed}`"));
+    lines.push(format!("`/verify {round_id}` でシャッフルを検証できます。"));
+    lines.push(format!("`/hand {round_id}` でこのラウンドを確認できます。"));
+    lines
+}
+
+// 進行を1行ずつの文字列にする
+pub struct TextRenderer {
+    rules: TableRules,
+    style: CardStyle,
+    lines: Vec<String>,
+}
+
+impl TextRenderer {
+    pub fn new(rules: TableRules, style: CardStyle) -> TextRenderer {
+        TextRenderer {
+            rules,
+            style,
+            lines: vec![],
+        }
+    }
+
+    pub fn get_lines(&self) -> &[String] {
+        &self.lines
+    }
+}
+
+#[async_trait]
+impl TableObserver for TextRenderer {
+    async fn observe(&mut self, effect: &Effect, state: &State, round: &RoundInfo) {
+        let style = self.style;
+        match effect {
+            Effect::Init(players) => {
+                self.lines.push(format!(
+                    "{}秒後に次のゲームを始めます。",
+                    self.rules.betting_time
+                ));
+                self.lines
+                    .push(format!("現在の参加者: {}", players.join(", ")));
+            }
+            Effect::Deal(..) => {
+                self.lines.push("カードを配布します。".to_string());
+                self.lines
+                    .extend(state.format(style).lines().map(str::to_string));
+            }
+            Effect::AddCard(name, _) => {
+                let player = state.get_player(name).unwrap();
+                self.lines.push(player.format(style));
+            }
+            Effect::AddDealerCard(_) | Effect::OpenDealerCard(_) => {
+                self.lines.push(state.get_dealer().format(style));
+            }
+            Effect::NextPlayer => self.lines.push(match state.get_current_player() {
+                Some(player) => format!("{}さんのターンです。", player.name),
+                None => "ディーラーのターンです。".to_string(),
+            }),
+            Effect::Reveal(server_seed, client_seed) => {
+                self.lines.extend(result_lines(
+                    state,
+                    &self.rules,
+                    round,
+                    (server_seed, client_seed),
+                ));
+            }
+            _ => {}
+        }
+        self.lines.extend(describe(effect));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::card::Card;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    fn card(s: &str) -> Card {
+        Card::from_str(s).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_text_renderer() {
+        let effects = vec![
+            Effect::Init(vec!["a".to_string()]),
+            Effect::Bet("a".to_string(), 10),
+            Effect::Start,
+            Effect::Deal(
+                HashMap::from([("a".to_string(), (card("SK"), card("H6")))]),
+                (card("S10"), Card::new_hidden()),
+            ),
+            Effect::NextPlayer,
+            Effect::AddCard("a".to_string(), card("D9")),
+            Effect::Burst("a".to_string()),
+            Effect::NextPlayer,
+            Effect::OpenDealerCard(card("C7")),
+            Effect::Finish,
+            Effect::Reveal("server".to_string(), "client".to_string()),
+        ];
+
+        let mut renderer = TextRenderer::new(TableRules::default(), CardStyle::Code);
+        let mut state = State::new();
+        let round = RoundInfo {
+            round_id: 1,
+            settled: true,
+        };
+        for effect in effects {
+            state.apply_effect(effect.clone());
+            renderer.observe(&effect, &state, &round).await;
+        }
+
+        let lines = renderer.get_lines();
+        assert!(lines.contains(&"a: SK H6 D9 (25)".to_string()));
+        assert!(lines.contains(&"aさんはバーストしました。".to_string()));
+        assert!(lines.contains(&"ディーラー: S10 C7 (17)".to_string()));
+        assert!(lines.contains(&"a: 0 (-10)".to_string()));
+    }
+}
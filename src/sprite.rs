@@ -0,0 +1,146 @@
+use std::io::Cursor;
+use std::sync::OnceLock;
+
+use image::{imageops, ImageFormat, Rgba, RgbaImage};
+
+use crate::game::card::{Card, Suit, Value};
+use crate::game::state::State;
+
+// 同梱したカードの画像。1行に1つのスートをAからKの順に並べ、最後の行の先頭が裏面
+const SPRITES: &[u8] = include_bytes!("../assets/cards.png");
+const CARD_WIDTH: u32 = 64;
+const CARD_HEIGHT: u32 = 90;
+// カードやハンドの間の余白
+const CARD_GAP: u32 = 4;
+const HAND_GAP: u32 = 24;
+const MARGIN: u32 = 12;
+const BACKGROUND: Rgba<u8> = Rgba([31, 99, 62, 255]);
+
+fn sheet() -> &'static RgbaImage {
+    static SHEET: OnceLock<RgbaImage> = OnceLock::new();
+    SHEET.get_or_init(|| {
+        image::load_from_memory_with_format(SPRITES, ImageFormat::Png)
+            .unwrap()
+            .to_rgba8()
+    })
+}
+
+// シートの中のカードの位置。伏せたカードは裏面になる
+fn sprite_position(card: &Card) -> (u32, u32) {
+    let row = match card.get_suit() {
+        Suit::Spade => 0,
+        Suit::Heart => 1,
+        Suit::Diamond => 2,
+        Suit::Club => 3,
+        Suit::Hidden => return (0, 4),
+    };
+    let column = match card.get_value() {
+        Value::Ace => 0,
+        Value::Two => 1,
+        Value::Three => 2,
+        Value::Four => 3,
+        Value::Five => 4,
+        Value::Six => 5,
+        Value::Seven => 6,
+        Value::Eight => 7,
+        Value::Nine => 8,
+        Value::Ten => 9,
+        Value::Jack => 10,
+        Value::Queen => 11,
+        Value::King => 12,
+        Value::Hidden => return (0, 4),
+    };
+    (column, row)
+}
+
+fn hand_width(cards: &[Card]) -> u32 {
+    let count = cards.len() as u32;
+    count * CARD_WIDTH + count.saturating_sub(1) * CARD_GAP
+}
+
+// 1行に1人分のハンドを並べた画像を作る。スプリットしたハンドは同じ行に並べる
+pub fn render_rows(rows: &[Vec<Vec<Card>>]) -> RgbaImage {
+    let width = rows
+        .iter()
+        .map(|hands| {
+            hands.iter().map(|cards| hand_width(cards)).sum::<u32>()
+                + hands.len().saturating_sub(1) as u32 * HAND_GAP
+        })
+        .max()
+        .unwrap_or(0);
+    let height = rows.len() as u32 * CARD_HEIGHT + rows.len().saturating_sub(1) as u32 * CARD_GAP;
+    let mut image = RgbaImage::from_pixel(width + MARGIN * 2, height + MARGIN * 2, BACKGROUND);
+
+    let sheet = sheet();
+    for (row, hands) in rows.iter().enumerate() {
+        let y = MARGIN + row as u32 * (CARD_HEIGHT + CARD_GAP);
+        let mut x = MARGIN;
+        for cards in hands {
+            for card in cards {
+                let (column, sheet_row) = sprite_position(card);
+                let sprite = imageops::crop_imm(
+                    sheet,
+                    column * CARD_WIDTH,
+                    sheet_row * CARD_HEIGHT,
+                    CARD_WIDTH,
+                    CARD_HEIGHT,
+                );
+                imageops::overlay(&mut image, &*sprite, x as i64, y as i64);
+                x += CARD_WIDTH + CARD_GAP;
+            }
+            x += HAND_GAP - CARD_GAP;
+        }
+    }
+    image
+}
+
+// ディーラーと各プレイヤーのハンドを席順に並べたPNG。まだカードが配られていなければNone
+pub fn render_table(state: &State) -> Option<Vec<u8>> {
+    let mut rows = vec![vec![state.get_dealer_hands(false)]];
+    for name in state.get_player_order() {
+        let player = state.get_player(&name).unwrap();
+        rows.push(
+            (0..player.get_hand_count())
+                .map(|index| player.get_hand(index).get_cards())
+                .collect(),
+        );
+    }
+    if rows.iter().flatten().all(|cards| cards.is_empty()) {
+        return None;
+    }
+
+    let mut png = Cursor::new(vec![]);
+    render_rows(&rows)
+        .write_to(&mut png, ImageFormat::Png)
+        .unwrap();
+    Some(png.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_render_rows() {
+        let card = |s: &str| Card::from_str(s).unwrap();
+        let rows = vec![
+            vec![vec![card("SA"), Card::new_hidden()]],
+            vec![vec![card("H8"), card("D3")], vec![card("C8")]],
+        ];
+        let image = render_rows(&rows);
+        assert_eq!(
+            image.width(),
+            MARGIN * 2 + CARD_WIDTH * 3 + CARD_GAP + HAND_GAP
+        );
+        assert_eq!(image.height(), MARGIN * 2 + CARD_HEIGHT * 2 + CARD_GAP);
+
+        // 伏せたカードは裏面の画像になる
+        let x = MARGIN + CARD_WIDTH + CARD_GAP + CARD_WIDTH / 2;
+        let y = MARGIN + CARD_HEIGHT / 2;
+        assert_eq!(
+            image.get_pixel(x, y),
+            sheet().get_pixel(CARD_WIDTH / 2, CARD_HEIGHT * 4 + CARD_HEIGHT / 2)
+        );
+    }
+}